@@ -0,0 +1,544 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use clap::ArgMatches;
+use md5::Md5;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
+use reqwest::{Client, Request, Response, StatusCode};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+use crate::hosts::host_key;
+use crate::tls::PinnedKeys;
+
+// Identifiants d'un hôte
+#[derive(Clone, Debug)]
+enum Credentials {
+    // `None` : --user sans mot de passe, complété par le `.netrc` de l'hôte demandé
+    Password { user: String, password: Option<String> },
+    Bearer(String),
+}
+
+// Authentification HTTP : identifiants donnés en ligne de commande, sinon ceux du
+// `.netrc` pour l'hôte demandé
+#[derive(Clone, Debug, Default)]
+pub struct Auth {
+    explicit: Option<Credentials>,
+    netrc: Vec<NetrcEntry>,
+    // Hôtes ayant déjà réclamé une authentification Basic : inutile d'attendre
+    // un nouveau 401 pour chaque requête
+    basic_hosts: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Auth {
+    // --user/--password (ou --ask-password), --bearer-token, et le `.netrc`
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, String> {
+        let netrc = load_netrc()?;
+
+        let explicit = if let Some(token) = matches.get_one::<String>("bearer_token") {
+            Some(Credentials::Bearer(token.clone()))
+        } else if let Some(user) = matches.get_one::<String>("user") {
+            let password = if let Some(password) = matches.get_one::<String>("password") {
+                Some(password.clone())
+            } else if matches.get_flag("ask_password") {
+                let password = rpassword::prompt_password(format!("Password for user '{}': ", user))
+                    .map_err(|e| format!("Cannot read password: {}", e))?;
+                Some(password)
+            } else {
+                None
+            };
+            Some(Credentials::Password {
+                user: user.clone(),
+                password,
+            })
+        } else {
+            None
+        };
+
+        Ok(Auth {
+            explicit,
+            netrc,
+            basic_hosts: Arc::default(),
+        })
+    }
+
+    // Les identifiants donnés en ligne de commande ne valent que pour l'hôte demandé
+    // (`explicit_allowed` est faux après une redirection vers un autre hôte) ;
+    // ceux du `.netrc` sont propres à chaque hôte
+    fn credentials_for(&self, url: &Url, explicit_allowed: bool) -> Option<Credentials> {
+        let host = url.host_str().unwrap_or_default();
+        match self.explicit.as_ref().filter(|_| explicit_allowed) {
+            // Le mot de passe d'un autre hôte n'est jamais envoyé à celui-ci
+            Some(Credentials::Password { user, password: None }) => Some(Credentials::Password {
+                user: user.clone(),
+                password: self.netrc_entry(host, Some(user)).map(|entry| entry.password.clone()),
+            }),
+            Some(credentials) => Some(credentials.clone()),
+            None => self.netrc_entry(host, None).map(|entry| Credentials::Password {
+                user: entry.login.clone(),
+                password: Some(entry.password.clone()),
+            }),
+        }
+    }
+
+    // Entrée `machine` de l'hôte, sinon `default` ; restreinte à `login` s'il est donné
+    fn netrc_entry(&self, host: &str, login: Option<&str>) -> Option<&NetrcEntry> {
+        let candidates = || {
+            self.netrc
+                .iter()
+                .filter(move |entry| login.is_none_or(|login| entry.login == login))
+        };
+        candidates()
+            .find(|entry| entry.machine.as_deref() == Some(host))
+            .or_else(|| candidates().find(|entry| entry.machine.is_none()))
+    }
+
+    // Envoie la requête en répondant au défi d'un `401` (Basic ou Digest). Avec
+    // --pinnedpubkey, chaque réponse est vérifiée, et le `401` l'est avant que les
+    // identifiants ne partent.
+    pub async fn send(
+        &self,
+        client: &Client,
+        mut request: Request,
+        explicit_allowed: bool,
+        pinned_keys: Option<&PinnedKeys>,
+    ) -> Result<Response, Box<dyn Error>> {
+        let execute = async |request: Request| -> Result<Response, Box<dyn Error>> {
+            let response = client.execute(request).await?;
+            if let Some(pinned_keys) = pinned_keys {
+                pinned_keys.verify(&response)?;
+            }
+            Ok(response)
+        };
+
+        let url = request.url().clone();
+        let (user, password) = match self.credentials_for(&url, explicit_allowed) {
+            None => return execute(request).await,
+            Some(Credentials::Bearer(token)) => {
+                if let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", token)) {
+                    request.headers_mut().insert(AUTHORIZATION, value);
+                }
+                return execute(request).await;
+            }
+            Some(Credentials::Password { user, password }) => (user, password.unwrap_or_default()),
+        };
+
+        let sent_basic = self.basic_hosts.lock().unwrap().contains(&host_key(&url));
+        if sent_basic {
+            request.headers_mut().insert(AUTHORIZATION, basic_value(&user, &password));
+        }
+
+        let retry = request.try_clone();
+        let method = request.method().clone();
+        let response = execute(request).await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+        let Some(mut retry) = retry else {
+            return Ok(response);
+        };
+
+        let value = match parse_challenge(response.headers()) {
+            Some(Challenge::Digest(params)) => {
+                digest_value(&params, &user, &password, method.as_str(), &url)
+            }
+            Some(Challenge::Basic) if !sent_basic => {
+                self.basic_hosts.lock().unwrap().insert(host_key(&url));
+                Some(basic_value(&user, &password))
+            }
+            _ => None,
+        };
+        let Some(value) = value else {
+            return Ok(response);
+        };
+
+        retry.headers_mut().insert(AUTHORIZATION, value);
+        execute(retry).await
+    }
+}
+
+fn basic_value(user: &str, password: &str) -> HeaderValue {
+    let encoded = STANDARD.encode(format!("{}:{}", user, password));
+    let mut value = HeaderValue::from_str(&format!("Basic {}", encoded)).unwrap();
+    value.set_sensitive(true);
+    value
+}
+
+// Défi annoncé par WWW-Authenticate ; Digest est préféré à Basic
+enum Challenge {
+    Digest(Vec<(String, String)>),
+    Basic,
+}
+
+fn parse_challenge(headers: &HeaderMap) -> Option<Challenge> {
+    let mut basic = false;
+    for value in headers.get_all(WWW_AUTHENTICATE) {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        let (scheme, params) = value.trim().split_once(' ').unwrap_or((value.trim(), ""));
+        if scheme.eq_ignore_ascii_case("digest") {
+            return Some(Challenge::Digest(parse_params(params)));
+        }
+        if scheme.eq_ignore_ascii_case("basic") {
+            basic = true;
+        }
+    }
+    basic.then_some(Challenge::Basic)
+}
+
+// `clé=valeur, clé="valeur avec, virgule"` (clés en minuscules)
+fn parse_params(value: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut chars = value.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|&c| c == ',' || c.is_whitespace()) {
+            chars.next();
+        }
+        let key: String = chars.by_ref().take_while(|&c| c != '=').collect();
+        let key = key.trim().to_ascii_lowercase();
+        if key.is_empty() {
+            break;
+        }
+        let mut param = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => param.extend(chars.next()),
+                    '"' => break,
+                    _ => param.push(c),
+                }
+            }
+        } else {
+            param = chars.by_ref().take_while(|&c| c != ',').collect();
+            param = param.trim().to_string();
+        }
+        params.push((key, param));
+    }
+    params
+}
+
+// Réponse au défi Digest (RFC 7616), avec qop=auth si le serveur le propose
+fn digest_value(
+    params: &[(String, String)],
+    user: &str,
+    password: &str,
+    method: &str,
+    url: &Url,
+) -> Option<HeaderValue> {
+    let header = digest_header(params, user, password, method, url, &cnonce())?;
+    let mut value = HeaderValue::from_str(&header).ok()?;
+    value.set_sensitive(true);
+    Some(value)
+}
+
+// En-tête Authorization pour un `cnonce` donné
+fn digest_header(
+    params: &[(String, String)],
+    user: &str,
+    password: &str,
+    method: &str,
+    url: &Url,
+    cnonce: &str,
+) -> Option<String> {
+    let param = |name: &str| params.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+    let realm = param("realm").unwrap_or_default();
+    let nonce = param("nonce")?;
+    let algorithm = param("algorithm").unwrap_or("MD5");
+    let qop_auth = param("qop").is_some_and(|qop| qop.split(',').any(|q| q.trim() == "auth"));
+
+    let hash: fn(&str) -> String = match algorithm.to_ascii_uppercase().trim_end_matches("-SESS") {
+        "MD5" => |data| hex(&Md5::digest(data.as_bytes())),
+        "SHA-256" => |data| hex(&Sha256::digest(data.as_bytes())),
+        _ => return None,
+    };
+
+    let uri = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    let nc = "00000001";
+
+    let mut ha1 = hash(&format!("{}:{}:{}", user, realm, password));
+    if algorithm.to_ascii_lowercase().ends_with("-sess") {
+        ha1 = hash(&format!("{}:{}:{}", ha1, nonce, cnonce));
+    }
+    let ha2 = hash(&format!("{}:{}", method, uri));
+    let response = if qop_auth {
+        hash(&format!("{}:{}:{}:{}:auth:{}", ha1, nonce, nc, cnonce, ha2))
+    } else {
+        hash(&format!("{}:{}:{}", ha1, nonce, ha2))
+    };
+
+    let mut header = format!(
+        "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}, response=\"{}\"",
+        user, realm, nonce, uri, algorithm, response
+    );
+    if qop_auth {
+        header.push_str(&format!(", qop=auth, nc={}, cnonce=\"{}\"", nc, cnonce));
+    }
+    if let Some(opaque) = param("opaque") {
+        header.push_str(&format!(", opaque=\"{}\"", opaque));
+    }
+    Some(header)
+}
+
+// Valeur unique par requête, sans besoin d'un générateur cryptographique
+fn cnonce() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    hex(&Md5::digest(format!("{}:{}:{}", nanos, count, std::process::id()).as_bytes()))[..16].to_string()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Entrée du `.netrc` ; `machine` vaut `None` pour l'entrée `default`
+#[derive(Clone, Debug)]
+struct NetrcEntry {
+    machine: Option<String>,
+    login: String,
+    password: String,
+}
+
+// `$NETRC`, sinon `~/.netrc` ; un fichier absent n'est pas une erreur
+fn load_netrc() -> Result<Vec<NetrcEntry>, String> {
+    let path = match std::env::var_os("NETRC") {
+        Some(path) => PathBuf::from(path),
+        None => match std::env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".netrc"),
+            None => return Ok(Vec::new()),
+        },
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => Ok(parse_netrc(&contents)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("Cannot read {}: {}", path.display(), e)),
+    }
+}
+
+// Jetons `machine`/`default`, `login`, `password` ; les macros (`macdef`) sont
+// ignorées jusqu'à la ligne vide qui les termine
+fn parse_netrc(contents: &str) -> Vec<NetrcEntry> {
+    let mut entries: Vec<NetrcEntry> = Vec::new();
+    let mut lines = contents.lines();
+
+    while let Some(line) = lines.next() {
+        let mut tokens = line.split_whitespace();
+        while let Some(token) = tokens.next() {
+            match token {
+                "machine" | "default" => entries.push(NetrcEntry {
+                    machine: if token == "machine" {
+                        tokens.next().map(str::to_string)
+                    } else {
+                        None
+                    },
+                    login: String::new(),
+                    password: String::new(),
+                }),
+                "login" | "password" => {
+                    let (Some(value), Some(entry)) = (tokens.next(), entries.last_mut()) else {
+                        continue;
+                    };
+                    if token == "login" {
+                        entry.login = value.to_string();
+                    } else {
+                        entry.password = value.to_string();
+                    }
+                }
+                "macdef" => {
+                    for line in lines.by_ref() {
+                        if line.trim().is_empty() {
+                            break;
+                        }
+                    }
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Valeur d'un champ de l'en-tête Authorization produit
+    fn field<'a>(header: &'a str, name: &str) -> Option<&'a str> {
+        let params = header.strip_prefix("Digest ")?;
+        params.split(", ").find_map(|param| {
+            let (key, value) = param.split_once('=')?;
+            (key == name).then(|| value.trim_matches('"'))
+        })
+    }
+
+    fn challenge(value: &str) -> Vec<(String, String)> {
+        let mut headers = HeaderMap::new();
+        headers.insert(WWW_AUTHENTICATE, HeaderValue::from_str(value).unwrap());
+        match parse_challenge(&headers) {
+            Some(Challenge::Digest(params)) => params,
+            _ => panic!("expected a Digest challenge"),
+        }
+    }
+
+    // RFC 7616, section 3.9.1
+    const RFC7616_CHALLENGE: &str = "Digest realm=\"http-auth@example.org\", qop=\"auth, auth-int\", \
+        algorithm=ALGO, nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+        opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"";
+    const RFC7616_CNONCE: &str = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+
+    fn rfc7616_response(algorithm: &str) -> String {
+        let params = challenge(&RFC7616_CHALLENGE.replace("ALGO", algorithm));
+        let url = Url::parse("http://www.example.org/dir/index.html").unwrap();
+        digest_header(&params, "Mufasa", "Circle of Life", "GET", &url, RFC7616_CNONCE).unwrap()
+    }
+
+    #[test]
+    fn rfc7616_md5() {
+        let header = rfc7616_response("MD5");
+        assert_eq!(field(&header, "response"), Some("8ca523f5e9506fed4657c9700eebdbec"));
+        assert_eq!(field(&header, "uri"), Some("/dir/index.html"));
+        assert_eq!(field(&header, "qop"), Some("auth"));
+        assert_eq!(field(&header, "nc"), Some("00000001"));
+        assert_eq!(field(&header, "opaque"), Some("FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS"));
+    }
+
+    #[test]
+    fn rfc7616_sha256() {
+        let header = rfc7616_response("SHA-256");
+        assert_eq!(
+            field(&header, "response"),
+            Some("753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1")
+        );
+    }
+
+    // -sess : HA1 = H(H(user:realm:password):nonce:cnonce)
+    #[test]
+    fn sess_variant_rehashes_ha1() {
+        let header = rfc7616_response("MD5-sess");
+        let md5 = |data: String| hex(&Md5::digest(data.as_bytes()));
+        let nonce = "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v";
+        let ha1 = md5(format!(
+            "{}:{}:{}",
+            md5("Mufasa:http-auth@example.org:Circle of Life".to_string()),
+            nonce,
+            RFC7616_CNONCE
+        ));
+        let ha2 = md5("GET:/dir/index.html".to_string());
+        let expected = md5(format!("{}:{}:00000001:{}:auth:{}", ha1, nonce, RFC7616_CNONCE, ha2));
+        assert_eq!(field(&header, "response"), Some(expected.as_str()));
+        assert_eq!(field(&header, "algorithm"), Some("MD5-sess"));
+    }
+
+    // RFC 2617, section 3.5 : l'exemple historique en MD5
+    #[test]
+    fn rfc2617_md5() {
+        let params = challenge(
+            "Digest realm=\"testrealm@host.com\", qop=\"auth,auth-int\", \
+             nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", opaque=\"5ccc069c403ebaf9f0171e9517f40e41\"",
+        );
+        let url = Url::parse("http://www.nowhere.org/dir/index.html").unwrap();
+        let header = digest_header(&params, "Mufasa", "Circle Of Life", "GET", &url, "0a4f113b").unwrap();
+        assert_eq!(field(&header, "response"), Some("6629fae49393a05397450978507c4ef1"));
+    }
+
+    // Sans qop, la réponse est H(HA1:nonce:HA2) (compatibilité RFC 2069)
+    #[test]
+    fn digest_without_qop() {
+        let params = challenge("Digest realm=\"r\", nonce=\"n\"");
+        let url = Url::parse("http://example.com/a?b=c").unwrap();
+        let header = digest_header(&params, "u", "p", "GET", &url, "c").unwrap();
+        let md5 = |data: &str| hex(&Md5::digest(data.as_bytes()));
+        let expected = md5(&format!("{}:n:{}", md5("u:r:p"), md5("GET:/a?b=c")));
+        assert_eq!(field(&header, "response"), Some(expected.as_str()));
+        assert_eq!(field(&header, "qop"), None);
+    }
+
+    #[test]
+    fn unsupported_algorithm_is_refused() {
+        let params = challenge("Digest realm=\"r\", nonce=\"n\", algorithm=SHA-512-256");
+        let url = Url::parse("http://example.com/").unwrap();
+        assert_eq!(digest_header(&params, "u", "p", "GET", &url, "c"), None);
+    }
+
+    #[test]
+    fn digest_preferred_over_basic() {
+        let mut headers = HeaderMap::new();
+        headers.append(WWW_AUTHENTICATE, HeaderValue::from_static("Basic realm=\"r\""));
+        headers.append(WWW_AUTHENTICATE, HeaderValue::from_static("Digest realm=\"r\", nonce=\"n\""));
+        assert!(matches!(parse_challenge(&headers), Some(Challenge::Digest(_))));
+    }
+
+    #[test]
+    fn netrc_machines_default_and_macdef() {
+        let entries = parse_netrc(
+            "machine example.com login alice password s3cret\n\
+             macdef init\n\
+             cd /pub\n\
+             machine ignored login mallory password nope\n\
+             \n\
+             machine ftp.example.org\n\
+               login bob\n\
+               password hunter2\n\
+             default login anonymous password guest\n",
+        );
+        let summary: Vec<(Option<&str>, &str, &str)> = entries
+            .iter()
+            .map(|entry| (entry.machine.as_deref(), entry.login.as_str(), entry.password.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Some("example.com"), "alice", "s3cret"),
+                (Some("ftp.example.org"), "bob", "hunter2"),
+                (None, "anonymous", "guest"),
+            ]
+        );
+    }
+
+    #[test]
+    fn netrc_lookup_falls_back_to_default() {
+        let auth = Auth {
+            netrc: parse_netrc("machine example.com login alice password a\ndefault login anon password g\n"),
+            ..Auth::default()
+        };
+        let user_for = |url: &str| match auth.credentials_for(&Url::parse(url).unwrap(), true) {
+            Some(Credentials::Password { user, .. }) => user,
+            _ => String::new(),
+        };
+        assert_eq!(user_for("https://example.com/file"), "alice");
+        assert_eq!(user_for("https://other.example/file"), "anon");
+    }
+
+    #[test]
+    fn user_password_only_from_the_requested_host() {
+        let auth = Auth {
+            explicit: Some(Credentials::Password {
+                user: "alice".to_string(),
+                password: None,
+            }),
+            netrc: parse_netrc("machine bank.example login alice password BANKSECRET
+"),
+            ..Auth::default()
+        };
+        let password_for = |url: &str| match auth.credentials_for(&Url::parse(url).unwrap(), true) {
+            Some(Credentials::Password { password, .. }) => password,
+            _ => panic!("expected --user credentials"),
+        };
+        assert_eq!(password_for("https://bank.example/statement").as_deref(), Some("BANKSECRET"));
+        assert_eq!(password_for("http://127.0.0.1:8766/f"), None);
+    }
+}
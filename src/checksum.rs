@@ -0,0 +1,267 @@
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+
+use crate::utils::part_path;
+
+// Algorithmes acceptés par --checksum et dans les fichiers de sommes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+    Sha256,
+    Sha512,
+    Sha1,
+    Md5,
+    Blake3,
+}
+
+impl Algorithm {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().replace('-', "").as_str() {
+            "sha256" => Some(Algorithm::Sha256),
+            "sha512" => Some(Algorithm::Sha512),
+            "sha1" => Some(Algorithm::Sha1),
+            "md5" => Some(Algorithm::Md5),
+            "blake3" | "b3" => Some(Algorithm::Blake3),
+            _ => None,
+        }
+    }
+
+    // Sans précision, la longueur de l'empreinte suffit à deviner l'algorithme
+    fn from_hex_len(len: usize) -> Option<Self> {
+        match len {
+            32 => Some(Algorithm::Md5),
+            40 => Some(Algorithm::Sha1),
+            64 => Some(Algorithm::Sha256),
+            128 => Some(Algorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    pub fn hasher(self) -> Hasher {
+        match self {
+            Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            Algorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+            Algorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+            Algorithm::Md5 => Hasher::Md5(md5::Md5::new()),
+            Algorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Sha512 => "sha512",
+            Algorithm::Sha1 => "sha1",
+            Algorithm::Md5 => "md5",
+            Algorithm::Blake3 => "blake3",
+        };
+        f.write_str(name)
+    }
+}
+
+// Empreinte attendue pour un fichier
+#[derive(Clone, Debug)]
+pub struct Checksum {
+    pub algorithm: Algorithm,
+    pub expected: String,
+}
+
+impl Checksum {
+    // Format `algo:hex`, par exemple `sha256:9f86d0...`
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let (name, hex) = value
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid checksum '{}', expected algo:hex", value))?;
+        let algorithm =
+            Algorithm::parse(name).ok_or_else(|| format!("Unsupported checksum algorithm: {}", name))?;
+        Checksum::new(algorithm, hex)
+    }
+
+    fn new(algorithm: Algorithm, hex: &str) -> Result<Self, String> {
+        let hex = hex.trim().to_ascii_lowercase();
+        if hex.is_empty() || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Invalid {} checksum: {}", algorithm, hex));
+        }
+        Ok(Checksum { algorithm, expected: hex })
+    }
+
+    // Compare l'empreinte calculée à celle attendue
+    pub fn verify(&self, path: &Path, hasher: Hasher) -> Result<(), ChecksumMismatch> {
+        let actual = hasher.finalize();
+        if actual == self.expected {
+            Ok(())
+        } else {
+            Err(ChecksumMismatch {
+                path: path.display().to_string(),
+                algorithm: self.algorithm,
+                expected: self.expected.clone(),
+                actual,
+            })
+        }
+    }
+}
+
+// Calcul incrémental de l'empreinte, au fil des morceaux reçus
+pub enum Hasher {
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Sha1(Sha1),
+    Md5(md5::Md5),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Sha512(h) => h.update(data),
+            Hasher::Sha1(h) => h.update(data),
+            Hasher::Md5(h) => h.update(data),
+            Hasher::Blake3(h) => {
+                h.update(data);
+            }
+        }
+    }
+
+    pub fn finalize(self) -> String {
+        let digest = match self {
+            Hasher::Sha256(h) => h.finalize().to_vec(),
+            Hasher::Sha512(h) => h.finalize().to_vec(),
+            Hasher::Sha1(h) => h.finalize().to_vec(),
+            Hasher::Md5(h) => h.finalize().to_vec(),
+            Hasher::Blake3(h) => h.finalize().as_bytes().to_vec(),
+        };
+        digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    // Ajoute les `len` premiers octets d'un fichier (reprise d'un `.part`)
+    pub async fn update_from_file(&mut self, path: &Path, len: u64) -> std::io::Result<()> {
+        let mut file = File::open(path).await?.take(len);
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let read = file.read(&mut buffer).await?;
+            if read == 0 {
+                return Ok(());
+            }
+            self.update(&buffer[..read]);
+        }
+    }
+}
+
+// Empreinte différente de celle attendue
+#[derive(Debug)]
+pub struct ChecksumMismatch {
+    pub path: String,
+    pub algorithm: Algorithm,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} checksum mismatch for {}: expected {}, got {}",
+            self.algorithm, self.path, self.expected, self.actual
+        )
+    }
+}
+
+impl Error for ChecksumMismatch {}
+
+// Cherche l'empreinte de `file_name` dans un fichier de sommes, au format GNU
+// (`hex  nom` ou `hex *nom`) ou BSD (`SHA256 (nom) = hex`)
+pub fn lookup_in_file(sums_path: &Path, file_name: &str) -> Result<Option<Checksum>, String> {
+    let contents = std::fs::read_to_string(sums_path)
+        .map_err(|e| format!("Cannot read checksum file {}: {}", sums_path.display(), e))?;
+    // B3SUMS et consorts : empreintes BLAKE3 de 64 caractères, comme SHA-256
+    let sums_name = sums_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let blake3_file = sums_name.contains("b3") || sums_name.contains("blake3");
+
+    for line in contents.lines().map(str::trim) {
+        // Format BSD
+        if let Some((tag, rest)) = line.split_once(" (") {
+            if let Some((name, hex)) = rest.rsplit_once(") = ") {
+                if names_match(name, file_name) {
+                    let algorithm = Algorithm::parse(tag)
+                        .ok_or_else(|| format!("Unsupported checksum algorithm: {}", tag))?;
+                    return Checksum::new(algorithm, hex).map(Some);
+                }
+                continue;
+            }
+        }
+
+        // Format GNU
+        let Some((hex, name)) = line.split_once(char::is_whitespace) else {
+            continue;
+        };
+        let name = name.trim_start().trim_start_matches('*');
+        if !names_match(name, file_name) {
+            continue;
+        }
+        let algorithm = match Algorithm::from_hex_len(hex.len()) {
+            Some(Algorithm::Sha256) if blake3_file => Algorithm::Blake3,
+            Some(algorithm) => algorithm,
+            None => return Err(format!("Unrecognized checksum length for {}", file_name)),
+        };
+        return Checksum::new(algorithm, hex).map(Some);
+    }
+
+    Ok(None)
+}
+
+// Les fichiers de sommes contiennent souvent des chemins (`./dist/app.tar.gz`) :
+// seul le nom de base est comparé
+fn names_match(entry: &str, file_name: &str) -> bool {
+    entry == file_name || Path::new(entry).file_name().is_some_and(|name| name == file_name)
+}
+
+// Fichier corrompu : supprimé, ou déplacé dans le dossier de quarantaine.
+// Agit sur le `.part` s'il n'a pas encore été renommé.
+pub async fn discard(save_path: &Path, quarantine_dir: Option<&Path>) -> std::io::Result<()> {
+    let part = part_path(save_path);
+    let current = if part.exists() { part.as_path() } else { save_path };
+    match quarantine_dir {
+        Some(dir) => {
+            tokio::fs::create_dir_all(dir).await?;
+            let name = save_path.file_name().unwrap_or(save_path.as_os_str());
+            tokio::fs::rename(current, dir.join(name)).await
+        }
+        None => tokio::fs::remove_file(current).await,
+    }
+}
+
+// Vérifie l'empreinte calculée ; un fichier corrompu est écarté avant d'être exposé
+pub async fn check(
+    checksum: &Checksum,
+    hasher: Hasher,
+    save_path: &Path,
+    quarantine_dir: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    if let Err(mismatch) = checksum.verify(save_path, hasher) {
+        discard(save_path, quarantine_dir).await?;
+        return Err(Box::new(mismatch));
+    }
+    Ok(())
+}
+
+// Même vérification, en relisant un fichier déjà écrit (segments, fichier déjà complet)
+pub async fn check_file(
+    checksum: &Checksum,
+    file: &Path,
+    save_path: &Path,
+    quarantine_dir: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    let mut hasher = checksum.algorithm.hasher();
+    hasher.update_from_file(file, u64::MAX).await?;
+    check(checksum, hasher, save_path, quarantine_dir).await
+}
@@ -0,0 +1,29 @@
+use reqwest::redirect::Policy;
+use reqwest::{Client, ClientBuilder, Proxy};
+use std::sync::Arc;
+
+use crate::config::DownloadConfig;
+
+// Client unique, construit une fois dans `main` et partagé par tous les modes
+// (fichier unique, -i, --mirror) : mêmes cookies, proxy, TLS et délais partout,
+// et des connexions réutilisées d'une requête à l'autre. En-têtes, méthode et
+// authentification sont ajoutés à chaque requête par `RequestOptions`.
+pub fn build_client(config: &DownloadConfig) -> reqwest::Result<Client> {
+    // Les variables d'environnement sont déjà lues par ProxySettings, avec la gestion de no_proxy
+    let mut builder = ClientBuilder::new()
+        .cookie_provider(Arc::clone(&config.cookies))
+        .no_proxy()
+        // Les redirections sont suivies par RequestOptions, étape par étape
+        .redirect(Policy::none())
+        // Le certificat du serveur n'est conservé que pour vérifier --pinnedpubkey
+        .tls_info(config.request.pinned_keys.is_some());
+    builder = config.tls.apply(builder);
+    // Pas de délai global : un gros fichier peut légitimement prendre des heures,
+    // seule l'inactivité est limitée (--read-timeout)
+    builder = config.timeouts.apply(builder);
+    if !config.proxy.is_empty() {
+        let proxy = config.proxy.clone();
+        builder = builder.proxy(Proxy::custom(move |url| proxy.for_url(url)));
+    }
+    builder.build()
+}
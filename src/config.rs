@@ -0,0 +1,183 @@
+use clap::ArgMatches;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::checksum::{self, Checksum};
+use crate::cookies::CookieJar;
+use crate::mirror::MirrorOptions;
+use crate::naming::ClobberPolicy;
+use crate::pacing::Pacing;
+use crate::proxy::ProxySettings;
+use crate::report::Report;
+use crate::request::RequestOptions;
+use crate::retry::RetryPolicy;
+use crate::timeouts::Timeouts;
+use crate::tls::TlsSettings;
+
+// Téléchargements simultanés par défaut pour -i
+const DEFAULT_JOBS: usize = 4;
+
+// Options communes aux différents modes de téléchargement (-i, --mirror, fichier unique)
+#[derive(Clone, Debug, Default)]
+pub struct DownloadConfig {
+    // Reprendre un téléchargement interrompu (-c / --continue)
+    pub continue_download: bool,
+    // Nouvelles tentatives en cas d'erreur temporaire
+    pub retry: RetryPolicy,
+    // Nombre de connexions parallèles pour un fichier unique (--segments)
+    pub segments: usize,
+    // Ne retélécharger que si le fichier distant est plus récent (-N / --timestamping)
+    pub timestamping: bool,
+    // Nommer le fichier d'après l'en-tête Content-Disposition
+    pub content_disposition: bool,
+    // Nommer le fichier d'après l'URL finale, après redirection
+    pub trust_server_names: bool,
+    // Comportement quand le fichier existe déjà (--no-clobber, --backups)
+    pub clobber: ClobberPolicy,
+    // Empreinte attendue pour un fichier unique (--checksum algo:hex)
+    pub checksum: Option<Checksum>,
+    // Fichier de sommes où chercher l'empreinte par nom de fichier (--checksum-file)
+    pub checksum_file: Option<PathBuf>,
+    // Dossier où déplacer les fichiers corrompus au lieu de les supprimer
+    pub quarantine_dir: Option<PathBuf>,
+    // En-têtes, méthode et corps des requêtes (--header, --method, --post-data...)
+    pub request: RequestOptions,
+    // Cookies partagés par toutes les requêtes (--load-cookies)
+    pub cookies: Arc<CookieJar>,
+    // Fichier où enregistrer les cookies en fin d'exécution (--save-cookies)
+    pub save_cookies: Option<PathBuf>,
+    pub keep_session_cookies: bool,
+    // Proxy HTTP ou SOCKS (--proxy, variables d'environnement)
+    pub proxy: ProxySettings,
+    // Autorités, certificat client et version minimale de TLS
+    pub tls: TlsSettings,
+    // Délais de connexion, de résolution DNS et d'inactivité
+    pub timeouts: Timeouts,
+    // Téléchargements simultanés pour -i (--jobs), dont au plus `max_per_host` par serveur
+    pub jobs: usize,
+    pub max_per_host: Option<usize>,
+    // Profondeur de --mirror (-l, --page-requisites-depth)
+    pub mirror: MirrorOptions,
+    // Rythme des requêtes vers chaque serveur (--wait, --random-wait, --rate-per-host)
+    pub pacing: Arc<Pacing>,
+    // Résultat de chaque URL, pour le bilan final et --report-json
+    pub report: Arc<Report>,
+    pub report_json: Option<PathBuf>,
+}
+
+impl DownloadConfig {
+    // Construit la configuration à partir des arguments de la ligne de commande
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, String> {
+        let mut retry = RetryPolicy::default();
+        if let Some(tries) = matches.get_one::<String>("tries") {
+            retry.tries = parse_tries(tries)?;
+        }
+        if let Some(secs) = matches.get_one::<u64>("waitretry") {
+            retry.wait_retry = Duration::from_secs(*secs);
+        }
+        if let Some(codes) = matches.get_one::<String>("retry_on_http_error") {
+            retry.retry_on_http_error = parse_status_list(codes)?;
+        }
+
+        let clobber = if matches.get_flag("no_clobber") {
+            ClobberPolicy::NoClobber
+        } else if let Some(count) = matches.get_one::<u32>("backups") {
+            ClobberPolicy::Backups(*count)
+        } else {
+            ClobberPolicy::Number
+        };
+
+        let cookies = CookieJar::default();
+        if let Some(path) = matches.get_one::<String>("load_cookies") {
+            cookies
+                .load(path.as_ref())
+                .map_err(|e| format!("Cannot load cookies from {}: {}", path, e))?;
+        }
+
+        Ok(DownloadConfig {
+            continue_download: matches.get_flag("continue"),
+            retry,
+            segments: matches.get_one::<usize>("segments").copied().unwrap_or(1),
+            timestamping: matches.get_flag("timestamping"),
+            content_disposition: matches.get_flag("content_disposition"),
+            trust_server_names: matches.get_flag("trust_server_names"),
+            clobber,
+            checksum: matches
+                .get_one::<String>("checksum")
+                .map(|value| Checksum::parse(value))
+                .transpose()?,
+            checksum_file: matches.get_one::<String>("checksum_file").map(PathBuf::from),
+            quarantine_dir: matches.get_one::<String>("quarantine_dir").map(PathBuf::from),
+            request: RequestOptions::from_matches(matches)?,
+            cookies: Arc::new(cookies),
+            save_cookies: matches.get_one::<String>("save_cookies").map(PathBuf::from),
+            keep_session_cookies: matches.get_flag("keep_session_cookies"),
+            proxy: ProxySettings::from_matches(matches)?,
+            tls: TlsSettings::from_matches(matches)?,
+            timeouts: Timeouts::from_matches(matches),
+            jobs: matches.get_one::<usize>("jobs").copied().unwrap_or(DEFAULT_JOBS),
+            max_per_host: matches.get_one::<usize>("max_per_host").copied(),
+            mirror: MirrorOptions::from_matches(matches)?,
+            pacing: Arc::new(Pacing::from_matches(matches)?),
+            report: Arc::default(),
+            report_json: matches.get_one::<String>("report_json").map(PathBuf::from),
+        })
+    }
+
+    // Enregistre le magasin de cookies si --save-cookies a été demandé
+    pub fn save_cookies(&self) -> std::io::Result<()> {
+        match &self.save_cookies {
+            Some(path) => self.cookies.save(path, self.keep_session_cookies),
+            None => Ok(()),
+        }
+    }
+
+    // Empreinte à vérifier pour `file_name` : celle donnée explicitement, sinon
+    // celle trouvée dans le fichier de sommes
+    pub fn checksum_for(&self, explicit: Option<&Checksum>, file_name: &str) -> Result<Option<Checksum>, String> {
+        if let Some(checksum) = explicit {
+            return Ok(Some(checksum.clone()));
+        }
+        match &self.checksum_file {
+            Some(path) => checksum::lookup_in_file(path, file_name),
+            None => Ok(None),
+        }
+    }
+
+    // Politique effective : un nom imposé par -O, ainsi que -c et -N, travaillent
+    // sur le fichier existant au lieu d'en créer un nouveau numéroté
+    pub fn clobber_policy(&self, explicit_name: bool) -> ClobberPolicy {
+        match self.clobber {
+            ClobberPolicy::Number
+                if explicit_name || self.continue_download || self.timestamping =>
+            {
+                ClobberPolicy::Overwrite
+            }
+            policy => policy,
+        }
+    }
+}
+
+// "inf" ou 0 : réessayer indéfiniment
+fn parse_tries(value: &str) -> Result<u32, String> {
+    if value.eq_ignore_ascii_case("inf") {
+        return Ok(0);
+    }
+    value
+        .parse()
+        .map_err(|_| format!("Invalid number of tries: {}", value))
+}
+
+// Liste de codes HTTP séparés par des virgules, ex. "503,429"
+fn parse_status_list(value: &str) -> Result<Vec<u16>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|code| !code.is_empty())
+        .map(|code| match code.parse::<u16>() {
+            Ok(status) if (100..600).contains(&status) => Ok(status),
+            _ => Err(format!("Invalid HTTP status code: {}", code)),
+        })
+        .collect()
+}
@@ -0,0 +1,431 @@
+use reqwest::cookie::CookieStore;
+use reqwest::header::HeaderValue;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::{Host, Url};
+
+use crate::hosts::in_domain;
+
+// Préfixe des cookies HttpOnly dans les fichiers exportés par les navigateurs
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+#[derive(Clone, Debug)]
+struct Cookie {
+    domain: String,
+    // Le cookie vaut aussi pour les sous-domaines (attribut Domain)
+    include_subdomains: bool,
+    path: String,
+    secure: bool,
+    http_only: bool,
+    // Date d'expiration en secondes Unix, `None` pour un cookie de session
+    expires: Option<u64>,
+    name: String,
+    value: String,
+}
+
+impl Cookie {
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let domain_ok = if self.include_subdomains {
+            in_domain(host, &self.domain)
+        } else {
+            host == self.domain
+        };
+        domain_ok && path_matches(url.path(), &self.path) && (!self.secure || url.scheme() == "https")
+    }
+
+    fn same_slot(&self, other: &Cookie) -> bool {
+        self.domain == other.domain && self.path == other.path && self.name == other.name
+    }
+}
+
+// Magasin de cookies partagé par toutes les requêtes d'une exécution, lisible et
+// enregistrable au format Netscape `cookies.txt`
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    cookies: Mutex<Vec<Cookie>>,
+}
+
+impl CookieJar {
+    // --load-cookies : ajoute les cookies encore valides d'un fichier `cookies.txt`
+    pub fn load(&self, path: &Path) -> io::Result<()> {
+        let contents = fs::read_to_string(path)?;
+        let now = now();
+        let mut cookies = self.cookies.lock().unwrap();
+        for cookie in contents.lines().filter_map(parse_netscape_line) {
+            if !cookie.is_expired(now) {
+                cookies.retain(|existing| !existing.same_slot(&cookie));
+                cookies.push(cookie);
+            }
+        }
+        Ok(())
+    }
+
+    // --save-cookies : les cookies de session ne sont écrits qu'avec --keep-session-cookies
+    pub fn save(&self, path: &Path, keep_session_cookies: bool) -> io::Result<()> {
+        let now = now();
+        let mut contents = String::from("# Netscape HTTP Cookie File\n\n");
+        for cookie in self.cookies.lock().unwrap().iter() {
+            if cookie.is_expired(now) || (cookie.expires.is_none() && !keep_session_cookies) {
+                continue;
+            }
+            let domain = if cookie.include_subdomains {
+                format!(".{}", cookie.domain)
+            } else {
+                cookie.domain.clone()
+            };
+            contents.push_str(&format!(
+                "{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                if cookie.http_only { HTTP_ONLY_PREFIX } else { "" },
+                domain,
+                flag(cookie.include_subdomains),
+                cookie.path,
+                flag(cookie.secure),
+                cookie.expires.unwrap_or(0),
+                cookie.name,
+                cookie.value
+            ));
+        }
+        fs::write(path, contents)
+    }
+}
+
+impl CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let now = now();
+        let mut cookies = self.cookies.lock().unwrap();
+        for header in cookie_headers {
+            let Some(cookie) = header.to_str().ok().and_then(|value| parse_set_cookie(value, url)) else {
+                continue;
+            };
+            cookies.retain(|existing| !existing.same_slot(&cookie));
+            // Une date passée sert à supprimer le cookie
+            if !cookie.is_expired(now) {
+                cookies.push(cookie);
+            }
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let now = now();
+        let cookies = self.cookies.lock().unwrap();
+        let mut matching: Vec<&Cookie> = cookies
+            .iter()
+            .filter(|cookie| !cookie.is_expired(now) && cookie.matches(url))
+            .collect();
+        if matching.is_empty() {
+            return None;
+        }
+        // Les chemins les plus précis d'abord (RFC 6265)
+        matching.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+        let header = matching
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        HeaderValue::from_str(&header).ok()
+    }
+}
+
+// `domaine  TRUE/FALSE  chemin  TRUE/FALSE  expiration  nom  valeur`, séparés par des tabulations
+fn parse_netscape_line(line: &str) -> Option<Cookie> {
+    let (line, http_only) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+        Some(rest) => (rest, true),
+        None => (line, false),
+    };
+    if line.starts_with('#') || line.trim().is_empty() {
+        return None;
+    }
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() != 7 {
+        return None;
+    }
+    let expires = fields[4].trim().parse::<u64>().ok()?;
+    Some(Cookie {
+        domain: fields[0].trim_start_matches('.').to_ascii_lowercase(),
+        include_subdomains: fields[1].eq_ignore_ascii_case("TRUE") || fields[0].starts_with('.'),
+        path: fields[2].to_string(),
+        secure: fields[3].eq_ignore_ascii_case("TRUE"),
+        http_only,
+        expires: (expires != 0).then_some(expires),
+        name: fields[5].to_string(),
+        value: fields[6].to_string(),
+    })
+}
+
+// En-tête Set-Cookie reçu depuis `url` ; un attribut Domain étranger à l'hôte est refusé
+fn parse_set_cookie(header: &str, url: &Url) -> Option<Cookie> {
+    let host = url.host_str()?.to_ascii_lowercase();
+    let mut parts = header.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut cookie = Cookie {
+        domain: host.clone(),
+        include_subdomains: false,
+        path: default_path(url),
+        secure: false,
+        http_only: false,
+        expires: None,
+        name: name.to_string(),
+        value: value.trim().trim_matches('"').to_string(),
+    };
+    let mut max_age = None;
+
+    for attribute in parts {
+        let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+        let value = value.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "domain" if !value.is_empty() => {
+                let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                if !in_domain(&host, &domain) {
+                    return None;
+                }
+                // Faute de liste des suffixes publics, un domaine sans point intérieur
+                // (`com`, `localhost`) en tient lieu, comme une adresse IP : le cookie
+                // reste propre à l'hôte qui l'envoie (RFC 6265, section 5.3, étape 5)
+                let ip = matches!(url.host(), Some(Host::Ipv4(_) | Host::Ipv6(_)));
+                if ip || !domain.contains('.') {
+                    if host != domain {
+                        return None;
+                    }
+                    continue;
+                }
+                cookie.domain = domain;
+                cookie.include_subdomains = true;
+            }
+            "path" if value.starts_with('/') => cookie.path = value.to_string(),
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            "max-age" => max_age = value.parse::<i64>().ok(),
+            "expires" => {
+                // `Wed, 21-Oct-2015 07:28:00 GMT` est aussi courant que la forme HTTP
+                cookie.expires = httpdate::parse_http_date(&value.replace('-', " "))
+                    .ok()
+                    .and_then(|date| date.duration_since(UNIX_EPOCH).ok())
+                    .map(|age| age.as_secs().max(1));
+            }
+            _ => {}
+        }
+    }
+
+    // Max-Age l'emporte sur Expires ; 0 ou négatif supprime le cookie
+    if let Some(age) = max_age {
+        cookie.expires = Some(if age <= 0 { 1 } else { now() + age as u64 });
+    }
+    Some(cookie)
+}
+
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+// Chemin par défaut : le « dossier » de l'URL (RFC 6265, section 5.1.4)
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(index) => url.path()[..index].to_string(),
+    }
+}
+
+fn flag(value: bool) -> &'static str {
+    if value {
+        "TRUE"
+    } else {
+        "FALSE"
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(value: &str) -> Url {
+        Url::parse(value).unwrap()
+    }
+
+    fn set(header: &str, from: &str) -> Option<Cookie> {
+        parse_set_cookie(header, &url(from))
+    }
+
+    // RFC 6265, section 3.1 : cookie sans attribut, réservé à l'hôte d'origine
+    #[test]
+    fn host_only_session_cookie() {
+        let cookie = set("SID=31d4d96e407aad42", "http://www.example.com/").unwrap();
+        assert_eq!((cookie.name.as_str(), cookie.value.as_str()), ("SID", "31d4d96e407aad42"));
+        assert_eq!(cookie.domain, "www.example.com");
+        assert!(!cookie.include_subdomains);
+        assert_eq!(cookie.path, "/");
+        assert_eq!(cookie.expires, None);
+        assert!(cookie.matches(&url("http://www.example.com/x")));
+        assert!(!cookie.matches(&url("http://docs.www.example.com/")));
+    }
+
+    #[test]
+    fn domain_attribute_covers_subdomains() {
+        let cookie = set("SID=31d4d96e407aad42; Path=/; Domain=example.com", "http://www.example.com/").unwrap();
+        assert_eq!(cookie.domain, "example.com");
+        assert!(cookie.include_subdomains);
+        assert!(cookie.matches(&url("http://docs.example.com/")));
+        assert!(cookie.matches(&url("http://example.com/")));
+        assert!(!cookie.matches(&url("http://notexample.com/")));
+        // Le point initial est ignoré (section 5.2.3)
+        let cookie = set("a=b; Domain=.example.com", "http://www.example.com/").unwrap();
+        assert_eq!(cookie.domain, "example.com");
+    }
+
+    #[test]
+    fn foreign_domain_is_rejected() {
+        assert!(set("a=b; Domain=evil.com", "http://www.example.com/").is_none());
+        assert!(set("a=b; Domain=ample.com", "http://www.example.com/").is_none());
+        assert!(set("a=b; Domain=www.example.com.evil", "http://www.example.com/").is_none());
+    }
+
+    #[test]
+    fn public_suffix_domain_is_rejected() {
+        assert!(set("a=b; Domain=com", "http://evil.com/").is_none());
+        assert!(set("a=b; Domain=.com", "http://evil.com/").is_none());
+
+        // Sur l'hôte lui-même, l'attribut est ignoré : le cookie reste propre à l'hôte
+        let cookie = set("a=b; Domain=localhost", "http://localhost/").unwrap();
+        assert_eq!(cookie.domain, "localhost");
+        assert!(!cookie.include_subdomains);
+    }
+
+    #[test]
+    fn ip_address_never_covers_other_hosts() {
+        assert!(set("a=b; Domain=0.0.1", "http://127.0.0.1/").is_none());
+        let cookie = set("a=b; Domain=127.0.0.1", "http://127.0.0.1/").unwrap();
+        assert!(!cookie.include_subdomains);
+    }
+
+    #[test]
+    fn secure_and_http_only() {
+        let cookie = set("SID=31d4d96e407aad42; Path=/; Secure; HttpOnly", "https://www.example.com/").unwrap();
+        assert!(cookie.secure && cookie.http_only);
+        assert!(cookie.matches(&url("https://www.example.com/")));
+        assert!(!cookie.matches(&url("http://www.example.com/")));
+    }
+
+    // RFC 6265, section 5.1.4
+    #[test]
+    fn default_path_and_path_match() {
+        assert_eq!(set("a=b", "http://example.com/docs/page").unwrap().path, "/docs");
+        assert_eq!(set("a=b", "http://example.com/page").unwrap().path, "/");
+        assert_eq!(set("a=b; Path=relative", "http://example.com/docs/page").unwrap().path, "/docs");
+
+        assert!(path_matches("/docs", "/docs"));
+        assert!(path_matches("/docs/", "/docs"));
+        assert!(path_matches("/docs/web", "/docs"));
+        assert!(path_matches("/docs/web", "/docs/"));
+        assert!(!path_matches("/docsx", "/docs"));
+        assert!(!path_matches("/", "/docs"));
+    }
+
+    // RFC 6265, section 3.1 : dates d'expiration, et suppression par une date passée
+    #[test]
+    fn expires_and_max_age() {
+        let cookie = set("lang=en-US; Expires=Wed, 09 Jun 2021 10:18:14 GMT", "http://example.com/").unwrap();
+        assert_eq!(cookie.expires, Some(1623233894));
+        let cookie = set("lang=; Expires=Sun, 06 Nov 1994 08:49:37 GMT", "http://example.com/").unwrap();
+        assert!(cookie.is_expired(now()));
+        // Forme à tirets, courante en pratique
+        let cookie = set("a=b; expires=Wed, 21-Oct-2015 07:28:00 GMT", "http://example.com/").unwrap();
+        assert_eq!(cookie.expires, Some(1445412480));
+
+        // Max-Age l'emporte sur Expires (section 5.3, étape 3)
+        let cookie = set("a=b; Max-Age=3600; Expires=Sun, 06 Nov 1994 08:49:37 GMT", "http://example.com/").unwrap();
+        assert!(!cookie.is_expired(now()));
+        let cookie = set("a=b; Max-Age=0", "http://example.com/").unwrap();
+        assert!(cookie.is_expired(now()));
+    }
+
+    #[test]
+    fn invalid_set_cookie() {
+        assert!(set("no-equals-sign", "http://example.com/").is_none());
+        assert!(set("=value", "http://example.com/").is_none());
+    }
+
+    #[test]
+    fn jar_sends_most_specific_path_first_and_replaces_slots() {
+        let jar = CookieJar::default();
+        let from = url("http://www.example.com/docs/page");
+        let headers = [
+            HeaderValue::from_static("root=1; Path=/"),
+            HeaderValue::from_static("docs=2; Path=/docs"),
+            HeaderValue::from_static("root=3; Path=/"),
+        ];
+        jar.set_cookies(&mut headers.iter(), &from);
+        let sent = jar.cookies(&url("http://www.example.com/docs/other")).unwrap();
+        assert_eq!(sent.to_str().unwrap(), "docs=2; root=3");
+        let sent = jar.cookies(&url("http://www.example.com/")).unwrap();
+        assert_eq!(sent.to_str().unwrap(), "root=3");
+    }
+
+    #[test]
+    fn netscape_lines() {
+        let cookie = parse_netscape_line(".example.com\tTRUE\t/\tFALSE\t2000000000\tsid\tabc").unwrap();
+        assert_eq!(cookie.domain, "example.com");
+        assert!(cookie.include_subdomains && !cookie.secure && !cookie.http_only);
+        assert_eq!(cookie.expires, Some(2000000000));
+        assert_eq!((cookie.name.as_str(), cookie.value.as_str()), ("sid", "abc"));
+
+        // Préfixe des cookies HttpOnly, à ne pas confondre avec un commentaire
+        let cookie = parse_netscape_line("#HttpOnly_www.example.com\tFALSE\t/app\tTRUE\t0\ttoken\txyz").unwrap();
+        assert!(cookie.http_only && cookie.secure && !cookie.include_subdomains);
+        assert_eq!(cookie.domain, "www.example.com");
+        assert_eq!(cookie.path, "/app");
+        assert_eq!(cookie.expires, None);
+
+        assert!(parse_netscape_line("# Netscape HTTP Cookie File").is_none());
+        assert!(parse_netscape_line("").is_none());
+        assert!(parse_netscape_line("example.com\tTRUE\t/\tFALSE\t0\tmissing-value").is_none());
+        assert!(parse_netscape_line("example.com\tTRUE\t/\tFALSE\tsoon\tname\tvalue").is_none());
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let path = std::env::temp_dir().join(format!("wget-cookies-{}.txt", std::process::id()));
+        let jar = CookieJar::default();
+        let from = url("https://www.example.com/");
+        let headers = [
+            HeaderValue::from_static("kept=1; Domain=example.com; Max-Age=3600; Secure; HttpOnly"),
+            HeaderValue::from_static("session=2"),
+        ];
+        jar.set_cookies(&mut headers.iter(), &from);
+
+        jar.save(&path, false).unwrap();
+        let loaded = CookieJar::default();
+        loaded.load(&path).unwrap();
+        let sent = loaded.cookies(&url("https://docs.example.com/")).map(|value| value.to_str().unwrap().to_string());
+        assert_eq!(sent.as_deref(), Some("kept=1"));
+        assert!(loaded.cookies.lock().unwrap()[0].http_only);
+
+        jar.save(&path, true).unwrap();
+        let loaded = CookieJar::default();
+        loaded.load(&path).unwrap();
+        assert_eq!(loaded.cookies.lock().unwrap().len(), 2);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,56 +1,86 @@
-use reqwest::Client;
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::{Client, Response, StatusCode};
 use indicatif::{ProgressBar, ProgressStyle};
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
-use tokio::time::{sleep, Duration};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::time::{Duration, Instant};
 use futures::stream::{FuturesUnordered, StreamExt};
-use std::fs::read_to_string;
 use std::error::Error;
+use std::path::Path;
 use chrono::Local;
+use url::Url;
 use std::fs;
 
 
-use crate::utils::log_to_file;
+use crate::checksum::{self, Checksum, Hasher};
+use crate::config::DownloadConfig;
+use crate::jobs::JobQueue;
+use crate::naming::{file_name_from_response, file_name_from_url, names_from_server, reserve_path};
+use crate::progress::BatchProgress;
+use crate::report::Entry;
+use crate::rate_limiter::{parse_rate_limit, RateLimiter};
+use crate::request::RequestOptions;
+use crate::retry::{HttpStatusError, RetryPolicy};
+use crate::segments::{probe_ranges, split_ranges, SegmentedDownload};
+use crate::timeouts::idle;
+use crate::timestamp;
+use crate::utils::{commit_part, part_path, Output};
 
 
 pub async fn download_single_file(
-    url: &str, 
-    file_name: Option<&str>, 
-    directory: Option<&str>, 
-    rate_limit: Option<&str>,  
-    background: bool  
+    client: &Client,
+    url: &str,
+    file_name: Option<&str>,
+    directory: Option<&str>,
+    rate_limit: Option<&str>,
+    background: bool,
+    config: &DownloadConfig,
 ) -> Result<(), Box<dyn Error>> {
+    let started = Instant::now();
+    let result = fetch_single_file(client, url, file_name, directory, rate_limit, background, config).await;
+    config.report.record(Entry::new(url, &result, started.elapsed()));
+    result.map(|_| ())
+}
 
-
-    let start_time = Local::now();
-    if !background{
-        println!("Start at: {}", start_time.format("%Y-%m-%d %H:%M:%S"));
+// Renvoie le chemin du fichier enregistré, ou `None` pour la sortie standard
+async fn fetch_single_file(
+    client: &Client,
+    url: &str,
+    file_name: Option<&str>,
+    directory: Option<&str>,
+    rate_limit: Option<&str>,
+    background: bool,
+    config: &DownloadConfig,
+) -> Result<Option<String>, Box<dyn Error>> {
+
+    // -O - : le contenu part sur la sortie standard, les messages sur la sortie d'erreur
+    let to_stdout = file_name == Some("-");
+    let output = if to_stdout && !background {
+        Output::Stderr
     } else {
-        log_to_file(&format!("Start at: {}", start_time.format("%Y-%m-%d %H:%M:%S")));
-    }
-    
-    let client = Client::builder()
-    .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/58.0.3029.110 Safari/537.3")
-    .timeout(Duration::from_secs(600))
-    .build()?;
-
-    let response = client.get(url).send().await?;
-
-    // Afficher le statut HTTP
-    let status = response.status();
+        Output::new(background)
+    };
 
-    if background{
-        log_to_file(&format!("sending request, awaiting response... status {}", status));
-    } else {
-        println!("sending request, awaiting response... status {}", status);
-    }
+    let start_time = Local::now();
+    output.print(&format!("Start at: {}", start_time.format("%Y-%m-%d %H:%M:%S")));
 
-    if !status.is_success() {
-        return Err(format!("Failed to download: {}. Status: {}", url, status).into());
+    if to_stdout {
+        let limiter = rate_limiter(rate_limit);
+        let pb = progress_bar(background);
+        download_to_stdout(client, url, output, pb.as_ref(), limiter.as_ref(), config).await?;
+        return Ok(None);
     }
 
-    let total_size = response.content_length().unwrap_or(0);
-    let file_name = file_name.unwrap_or_else(|| url.split('/').last().unwrap());
+    let policy = config.clobber_policy(file_name.is_some());
+    let (file_name, named_by) = match file_name {
+        Some(name) => (name.to_string(), None),
+        None => {
+            choose_file_name(client, url, config, output, |e, delay, next| {
+                output.print(&retry_message(e, delay, next, &config.retry))
+            })
+            .await?
+        }
+    };
     let save_path = if let Some(dir) = directory {
         format!("{}/{}", dir, file_name)
     } else {
@@ -59,139 +89,777 @@ pub async fn download_single_file(
         format!("downloads/{}", file_name)
     };
 
-    if background{
-        log_to_file(&format!("content size: {} [~{:.2}MB]", total_size, total_size as f64 / (1024.0 * 1024.0)));
-    } else {
-        println!("content size: {} [~{:.2}MB]", total_size, total_size as f64 / (1024.0 * 1024.0));
+    let reservation = match reserve_path(Path::new(&save_path), policy)? {
+        Some(reservation) => reservation,
+        None => {
+            output.print(&format!("File '{}' already there; not retrieving.", save_path));
+            return Ok(Some(save_path));
+        }
+    };
+    let save_path = reservation.path().to_string_lossy().into_owned();
+    let checksum = config.checksum_for(config.checksum.as_ref(), &file_name)?;
+
+    // Gestion du rate-limit (partagé entre tous les segments)
+    let limiter = rate_limiter(rate_limit);
+
+    // Barre de progression
+    let pb = progress_bar(background);
+
+    let mut target = Target {
+        url,
+        save_path: &save_path,
+        // Reprise : on repart de la taille du fichier déjà présent sur le disque
+        position: if config.continue_download {
+            resume_position(&save_path).await
+        } else {
+            0
+        },
+        timestamping: config.timestamping,
+        checksum: checksum.as_ref(),
+        quarantine_dir: config.quarantine_dir.as_deref(),
+        request: &config.request,
+        read_timeout: config.timeouts.read,
+        pending: None,
+    };
+
+    // Téléchargement segmenté si le serveur accepte les requêtes partielles.
+    // Avec -N et une copie locale, la requête conditionnelle passe par le flux unique,
+    // de même qu'une requête autre qu'un simple GET (--method, --post-data).
+    let has_local_copy = config.timestamping && Path::new(&save_path).exists();
+    let segmented = config.segments > 1 && target.position == 0 && !has_local_copy && config.request.is_plain_get();
+    // Les segments demandent chacun leur plage : la réponse qui a donné le nom ne sert pas
+    target.pending = reusable(named_by, &target).filter(|_| !segmented);
+    if segmented {
+        match probe_ranges(client, url, &config.request, config.timeouts.read).await {
+            Some(probe) => {
+                let total_size = probe.total_size;
+                let ranges = split_ranges(total_size, config.segments);
+                output.print(&format!("content size: {} [~{:.2}MB]", total_size, total_size as f64 / (1024.0 * 1024.0)));
+                output.print(&format!("saving file to: {} ({} segments)", save_path, ranges.len()));
+
+                let segmented = SegmentedDownload {
+                    client,
+                    url,
+                    request: &config.request,
+                    save_path: &save_path,
+                    pb: pb.as_ref(),
+                    limiter: limiter.as_ref(),
+                    retry: &config.retry,
+                    read_timeout: config.timeouts.read,
+                };
+                segmented.run(&ranges).await?;
+                // L'empreinte ne peut pas être calculée au fil de l'eau : les plages arrivent dans le désordre
+                if let Some(checksum) = &checksum {
+                    let part = part_path(Path::new(&save_path));
+                    checksum::check_file(checksum, &part, Path::new(&save_path), target.quarantine_dir).await?;
+                    output.print(&format!("{} checksum verified", checksum.algorithm));
+                }
+                commit_part(Path::new(&save_path)).await?;
+                if config.timestamping {
+                    timestamp::record(Path::new(&save_path), &probe.headers)?;
+                }
+
+                if let Some(pb) = &pb {
+                    pb.finish_with_message("Download complete");
+                }
+                output.print(&format!("Downloaded [{}]", url));
+                return Ok(Some(save_path));
+            }
+            None => output.print("Server does not support ranges, downloading in a single stream"),
+        }
     }
-    if background {
-        log_to_file(&format!("saving file to: {}", save_path));
-    } else {
-        println!("saving file to: {}", save_path);
+
+    let fetched = config
+        .retry
+        .run(
+            async || fetch_to_file(client, &mut target, output, pb.as_ref(), limiter.as_ref()).await,
+            |e, delay, next| output.print(&retry_message(e, delay, next, &config.retry)),
+        )
+        .await?;
+    match fetched {
+        Fetched::Complete => {}
+        Fetched::AlreadyComplete => {
+            output.print(&format!("The file is already fully retrieved: {}", save_path));
+            return Ok(Some(save_path));
+        }
+        Fetched::NotModified => {
+            output.print(&format!("Server file no newer than local file {} -- not retrieving.", save_path));
+            return Ok(Some(save_path));
+        }
     }
 
-    let mut file = File::create(&save_path).await.expect("Failed to create file");
-    let mut stream = response.bytes_stream();
-    let mut downloaded = 0;
+    if let Some(pb) = &pb {
+        pb.finish_with_message("Download complete");
+    }
+    output.print(&format!("Downloaded [{}]", url));
 
-  // Gestion du rate-limit
-  let rate_limit = if let Some(rate) = rate_limit {
-    match parse_rate_limit(rate) {
-        Ok(limit) => Some(limit),
+    // println!("Download completed: {}", save_path);
+    Ok(Some(save_path))
+}
+
+// Nom du fichier local pour `url`. S'il vient du serveur (--content-disposition,
+// --trust-server-names), c'est la requête de téléchargement elle-même qui le donne :
+// sa réponse est renvoyée pour que la première tentative la lise sans la redemander.
+async fn choose_file_name(
+    client: &Client,
+    url: &str,
+    config: &DownloadConfig,
+    output: Output,
+    on_retry: impl FnMut(&(dyn Error + 'static), Duration, u32),
+) -> Result<(String, Option<Response>), Box<dyn Error>> {
+    if !names_from_server(config) {
+        return Ok((file_name_from_url(url), None));
+    }
+    let attempt = async || -> Result<Response, Box<dyn Error>> {
+        let request = config.request.build(client, url);
+        let mut on_redirect = |location: &Url| {
+            output.print(&format!("Location: {} [following]", location));
+            Ok(())
+        };
+        let response = config.request.send_following(request, &mut on_redirect);
+        let response = idle(config.timeouts.read, response).await??;
+        if !response.status().is_success() {
+            return Err(HttpStatusError::from_response(url, &response).into());
+        }
+        Ok(response)
+    };
+    let response = config.retry.run(attempt, on_retry).await?;
+    Ok((file_name_from_response(&response, url, config), Some(response)))
+}
+
+// La réponse qui a donné le nom ne sert qu'à un téléchargement complet : pour
+// reprendre (-c) ou interroger le serveur sur une copie locale (-N), il faut une
+// nouvelle requête
+fn reusable(response: Option<Response>, target: &Target<'_>) -> Option<Response> {
+    let has_local_copy = target.timestamping && Path::new(target.save_path).exists();
+    response.filter(|_| target.position == 0 && !has_local_copy)
+}
+
+// "<erreur>. Retrying in 2.0s (attempt 2/20)..."
+fn retry_message(error: &dyn Error, delay: Duration, next: u32, retry: &RetryPolicy) -> String {
+    format!(
+        "{}. Retrying in {:.1}s (attempt {}/{})...",
+        error,
+        delay.as_secs_f32(),
+        next,
+        retry.tries_label()
+    )
+}
+
+fn rate_limiter(rate_limit: Option<&str>) -> Option<RateLimiter> {
+    match parse_rate_limit(rate_limit?) {
+        Ok(limit) => Some(RateLimiter::new(limit)),
         Err(e) => {
             eprintln!("Invalid rate limit: {}", e);
             None
         }
     }
-} else {
-    None
-};
+}
 
+// La barre est dessinée sur la sortie d'erreur, même avec -O -
+fn progress_bar(background: bool) -> Option<ProgressBar> {
+    if background {
+        return None;
+    }
+    let pb = ProgressBar::new(0);
+    let style = ProgressStyle::with_template("[{elapsed_precise}] {wide_bar} {bytes}/{total_bytes} ({eta}) {bytes_per_sec}")
+        .expect("valid progress template")
+        .progress_chars("=>-");
+    pb.set_style(style);
+    Some(pb)
+}
 
+// -O - : pas de fichier, donc ni `.part`, ni segments, ni -c/-N. Une tentative
+// interrompue reprend là où la sortie s'est arrêtée, sans rien réécrire.
+async fn download_to_stdout(
+    client: &Client,
+    url: &str,
+    output: Output,
+    pb: Option<&ProgressBar>,
+    limiter: Option<&RateLimiter>,
+    config: &DownloadConfig,
+) -> Result<(), Box<dyn Error>> {
+    let checksum = config.checksum_for(config.checksum.as_ref(), &file_name_from_url(url))?;
+    let mut hasher = checksum.as_ref().map(|checksum| checksum.algorithm.hasher());
+    let mut target = Target {
+        url,
+        save_path: STDOUT_NAME,
+        position: 0,
+        timestamping: false,
+        checksum: None,
+        quarantine_dir: None,
+        request: &config.request,
+        read_timeout: config.timeouts.read,
+        pending: None,
+    };
 
-    // Barre de progression
-    let pb = if !background {
-        let pb = ProgressBar::new(total_size);
-        let style = ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] {wide_bar} {bytes}/{total_bytes} ({eta}) {bytes_per_sec}")
-            .progress_chars("=>-");
-        pb.set_style(style);
-        Some(pb)
-    } else {
-        None
+    config
+        .retry
+        .run(
+            async || fetch_to_stdout(client, &mut target, output, pb, limiter, hasher.as_mut()).await,
+            |e, delay, next| output.print(&retry_message(e, delay, next, &config.retry)),
+        )
+        .await?;
+
+    if let Some(pb) = pb {
+        pb.finish_with_message("Download complete");
+    }
+    // Le contenu a déjà été transmis : on ne peut que signaler l'erreur
+    if let (Some(checksum), Some(hasher)) = (checksum, hasher) {
+        checksum.verify(Path::new(STDOUT_NAME), hasher)?;
+        output.print(&format!("{} checksum verified", checksum.algorithm));
+    }
+    output.print(&format!("Downloaded [{}]", url));
+    Ok(())
+}
+
+// Nom affiché pour la sortie standard
+const STDOUT_NAME: &str = "<stdout>";
+
+// Une tentative vers la sortie standard, à partir de `target.position`
+async fn fetch_to_stdout(
+    client: &Client,
+    target: &mut Target<'_>,
+    output: Output,
+    pb: Option<&ProgressBar>,
+    limiter: Option<&RateLimiter>,
+    mut hasher: Option<&mut Hasher>,
+) -> Result<(), Box<dyn Error>> {
+    let response = send_request(client, target, output).await?;
+    output.print(&format!("sending request, awaiting response... status {}", response.status()));
+
+    let transfer = match prepare_transfer(response, target.url, target.position)? {
+        Prepared::Transfer(transfer) => transfer,
+        // 416 sur une reprise : tout a déjà été écrit
+        Prepared::AlreadyComplete | Prepared::NotModified => return Ok(()),
+        // Les octets déjà écrits ne peuvent pas être repris
+        Prepared::ResumeRejected => {
+            return Err(format!("Server rejected resuming {} at byte {}", target.url, target.position).into())
+        }
+    };
+    // Un serveur qui ignore le Range renvoie tout : on saute ce qui a déjà été écrit
+    let mut skip = target.position - transfer.offset;
+
+    if let Some(pb) = pb {
+        pb.set_length(transfer.total_size);
+        pb.set_position(target.position);
+    }
+
+    let mut stdout = tokio::io::stdout();
+    let mut stream = transfer.response.bytes_stream();
+    while let Some(chunk) = idle(target.read_timeout, stream.next()).await? {
+        let chunk = chunk?;
+        let skipped = skip.min(chunk.len() as u64) as usize;
+        skip -= skipped as u64;
+        let data = &chunk[skipped..];
+
+        stdout.write_all(data).await?;
+        target.position += data.len() as u64;
+        if let Some(hasher) = hasher.as_mut() {
+            hasher.update(data);
+        }
+        if let Some(pb) = pb {
+            pb.set_position(target.position);
+        }
+        if let Some(limiter) = limiter {
+            limiter.consume(data.len()).await;
+        }
+    }
+    stdout.flush().await?;
+    Ok(())
+}
+
+// Fichier en cours de téléchargement et nombre d'octets déjà écrits
+struct Target<'a> {
+    url: &'a str,
+    save_path: &'a str,
+    position: u64,
+    // Mode -N : requête conditionnelle et date du serveur reportée sur le fichier
+    timestamping: bool,
+    // Empreinte attendue, vérifiée avant de renommer le `.part`
+    checksum: Option<&'a Checksum>,
+    quarantine_dir: Option<&'a Path>,
+    request: &'a RequestOptions,
+    // Inactivité tolérée avant d'abandonner la tentative (--read-timeout)
+    read_timeout: Option<Duration>,
+    // Réponse déjà reçue en choisissant le nom du fichier, lue par la première tentative
+    pending: Option<Response>,
+}
+
+// Issue d'une tentative de téléchargement
+enum Fetched {
+    Complete,
+    // Le fichier local était déjà complet (416 sur une reprise)
+    AlreadyComplete,
+    // La copie locale est à jour (304 en mode -N)
+    NotModified,
+}
+
+// Une tentative de téléchargement : reprend à `target.position` si possible
+async fn fetch_to_file(
+    client: &Client,
+    target: &mut Target<'_>,
+    output: Output,
+    pb: Option<&ProgressBar>,
+    limiter: Option<&RateLimiter>,
+) -> Result<Fetched, Box<dyn Error>> {
+    let offset = target.position;
+    let response = match target.pending.take() {
+        Some(response) => response,
+        None => send_request(client, target, output).await?,
     };
 
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk.expect("Error while downloading");
-        file.write_all(&chunk).await.expect("Failed to write to file");
-        downloaded += chunk.len() as u64;
+    // Afficher le statut HTTP
+    output.print(&format!("sending request, awaiting response... status {}", response.status()));
+
+    let transfer = match prepare_transfer(response, target.url, offset)? {
+        Prepared::Transfer(transfer) => transfer,
+        Prepared::AlreadyComplete => {
+            // Un `.part` complet n'attendait plus que d'être renommé
+            let save_path = Path::new(target.save_path);
+            let part = part_path(save_path);
+            let existing = if part.exists() { part.as_path() } else { save_path };
+            if let Some(checksum) = target.checksum {
+                checksum::check_file(checksum, existing, save_path, target.quarantine_dir).await?;
+                output.print(&format!("{} checksum verified", checksum.algorithm));
+            }
+            if part.exists() {
+                commit_part(save_path).await?;
+            }
+            return Ok(Fetched::AlreadyComplete);
+        }
+        // Copie locale plus longue que le fichier distant, ou taille inconnue : on
+        // ne peut rien en garder
+        Prepared::ResumeRejected => {
+            output.print("Local file does not match the server's size, restarting download from scratch");
+            target.position = 0;
+            return Box::pin(fetch_to_file(client, target, output, pb, limiter)).await;
+        }
+        Prepared::NotModified => return Ok(Fetched::NotModified),
+    };
+    if offset > 0 && transfer.offset == 0 {
+        output.print("Server does not support ranges, restarting download from scratch");
+    }
+
+    let total_size = transfer.total_size;
+    output.print(&format!("content size: {} [~{:.2}MB]", total_size, total_size as f64 / (1024.0 * 1024.0)));
+    output.print(&format!("saving file to: {}", target.save_path));
+    if transfer.offset > 0 {
+        output.print(&format!("resuming download at byte {}", transfer.offset));
+    }
+
+    let headers = transfer.response.headers().clone();
+    let mut file = open_output(target.save_path, transfer.offset).await?;
+    let mut stream = transfer.response.bytes_stream();
+    target.position = transfer.offset;
+
+    // Empreinte calculée au fil du téléchargement, en commençant par la partie déjà reçue
+    let mut hasher = target.checksum.map(|checksum| checksum.algorithm.hasher());
+    if let Some(hasher) = hasher.as_mut().filter(|_| target.position > 0) {
+        hasher
+            .update_from_file(&part_path(Path::new(target.save_path)), target.position)
+            .await?;
+    }
+
+    // La barre démarre à la position de reprise
+    if let Some(pb) = pb {
+        pb.set_length(total_size);
+        pb.set_position(target.position);
+        pb.reset_eta();
+    }
+
+    while let Some(chunk) = idle(target.read_timeout, stream.next()).await? {
+        let chunk = chunk?;
+        file.write_all(&chunk).await?;
+        target.position += chunk.len() as u64;
+        if let Some(hasher) = hasher.as_mut() {
+            hasher.update(&chunk);
+        }
 
         // Mise à jour de la barre de progression
-        if let Some(pb) = &pb {
-            pb.set_position(downloaded);
+        if let Some(pb) = pb {
+            pb.set_position(target.position);
         }
 
         // Gestion du débit (rate limiting)
-    if let Some(limit) = rate_limit {
-        let chunk_size = chunk.len();
-        let delay = chunk_size as u64 * 1500 / limit as u64; // Convertir en millisecondes
-        sleep(Duration::from_millis(delay)).await;
+        if let Some(limiter) = limiter {
+            limiter.consume(chunk.len()).await;
+        }
     }
+    file.flush().await?;
+    drop(file);
 
+    if let (Some(checksum), Some(hasher)) = (target.checksum, hasher) {
+        checksum::check(checksum, hasher, Path::new(target.save_path), target.quarantine_dir).await?;
+        output.print(&format!("{} checksum verified", checksum.algorithm));
     }
+    commit_part(Path::new(target.save_path)).await?;
 
-    if let Some(pb) = &pb {
-        pb.finish_with_message("Download complete");
-    }
-    if background {
-        log_to_file(&format!("Downloaded [{}]", url));
-    } else {
-        println!("Downloaded [{}]", url);
+    if target.timestamping {
+        timestamp::record(Path::new(target.save_path), &headers)?;
     }
 
-    // println!("Download completed: {}", save_path);
-    Ok(())
+    Ok(Fetched::Complete)
 }
 
 
 
-pub async fn download_multiple_files(file_path: &str) -> Result<(), Box<dyn Error>> {
-    let contents = read_to_string(file_path)?; 
-    let urls: Vec<&str> = contents.lines().collect(); 
-    
-    let client = Client::new();
+pub async fn download_multiple_files(client: &Client, file_path: &str, config: &DownloadConfig) -> Result<(), Box<dyn Error>> {
+    // `-i -` : les URL sont lues sur l'entrée standard au fur et à mesure, et chaque
+    // téléchargement démarre dès que sa ligne arrive
+    let reader: Box<dyn AsyncBufRead + Unpin> = if file_path == "-" {
+        Box::new(BufReader::new(tokio::io::stdin()))
+    } else {
+        Box::new(BufReader::new(File::open(file_path).await?))
+    };
+    let mut lines = reader.lines();
+    let mut input_done = false;
+
+    // Au plus --jobs téléchargements simultanés (et --max-per-host par serveur) ;
+    // la lecture s'interrompt tant que la file d'attente est pleine
+    let progress = BatchProgress::new();
+    let progress = &progress;
+    let mut ticker = tokio::time::interval(PROGRESS_TICK);
+    let mut queue = JobQueue::new(config.jobs, config.max_per_host);
     let mut futures = FuturesUnordered::new();
 
-    // Ajouter chaque téléchargement dans la file d'attente des tâches asynchrones
-    for url in urls {
-        let client = client.clone();
-        let file_name = url.split('/').last().unwrap_or("unknown").to_string();
+    // Lire les lignes et traiter les téléchargements terminés en même temps
+    loop {
+        while let Some((host, entry)) = queue.next_ready() {
+            futures.push(async move {
+                download_entry(client, entry, config, progress).await;
+                host
+            });
+        }
+        // Plus rien à lire ni à télécharger
+        if input_done && futures.is_empty() {
+            break;
+        }
 
-        futures.push(async move {
-            let result = download_file_async(&client, url).await;
-            match result {
-                Ok(_) => println!("Finished downloading {}", file_name),
-                Err(e) => eprintln!("Error downloading {}: {}", file_name, e),
-            }
-        });
+        tokio::select! {
+            line = lines.next_line(), if !input_done && queue.pending() < MAX_PENDING => match line? {
+                Some(line) => match parse_input_line(&line) {
+                    Some(Ok(entry)) => {
+                        progress.add_entry();
+                        queue.push(&entry.url.clone(), entry);
+                    }
+                    Some(Err(e)) => {
+                        progress.eprintln(&e);
+                        let url = line.split_whitespace().next().unwrap_or_default();
+                        config.report.record(Entry::new(url, &Err(e.into()), Duration::ZERO));
+                    }
+                    None => {}
+                },
+                None => input_done = true,
+            },
+            Some(host) = futures.next(), if !futures.is_empty() => queue.finish(&host),
+            _ = ticker.tick() => progress.tick(),
+        }
     }
+    progress.finish_all();
+    config.report.print_table();
+    Ok(())
+}
 
-    // Traiter chaque téléchargement
-    while futures.next().await.is_some() {}
+// Entrées lues d'avance au-delà des téléchargements en cours
+const MAX_PENDING: usize = 1024;
 
-    Ok(())
+// Rafraîchissement du total d'octets de la barre globale
+const PROGRESS_TICK: Duration = Duration::from_millis(200);
+
+// Télécharge une entrée du lot et inscrit son résultat au bilan
+async fn download_entry(client: &Client, entry: BatchEntry, config: &DownloadConfig, progress: &BatchProgress) {
+    // L'attente imposée par --wait ou --rate-per-host ne compte pas dans la durée du transfert
+    if let Ok(url) = Url::parse(&entry.url) {
+        config.pacing.wait_turn(&url.origin().ascii_serialization(), None).await;
+    }
+    let started = Instant::now();
+    let result = download_file_async(client, &entry, config, progress).await;
+    progress.entry_done();
+    if let Err(e) = &result {
+        progress.eprintln(&format!("Error downloading {}: {}", entry.url, e));
+    }
+    config.report.record(Entry::new(&entry.url, &result.map(Some), started.elapsed()));
 }
 
-async fn download_file_async(client: &Client, url: &str) -> Result<(), Box<dyn Error>> {
-   
-    fs::create_dir_all("downloads").expect("Failed to create downloads directory");
+// Ligne du fichier -i : une URL, éventuellement suivie de son empreinte (`algo:hex`)
+struct BatchEntry {
+    url: String,
+    checksum: Option<Checksum>,
+}
+
+// Les lignes vides et les commentaires (`#`) sont ignorés
+fn parse_input_line(line: &str) -> Option<Result<BatchEntry, String>> {
+    let mut fields = line.split_whitespace();
+    let url = fields.next().filter(|url| !url.starts_with('#'))?;
+    let checksum = match fields.next().map(Checksum::parse).transpose() {
+        Ok(checksum) => checksum,
+        Err(e) => return Some(Err(format!("{} (for {})", e, url))),
+    };
+    Some(Ok(BatchEntry {
+        url: url.to_string(),
+        checksum,
+    }))
+}
+
+// Télécharge une entrée dans `downloads/`, avec sa propre barre dans l'affichage du lot
+async fn download_file_async(client: &Client, entry: &BatchEntry, config: &DownloadConfig, progress: &BatchProgress) -> Result<String, Box<dyn Error>> {
 
-   
-    let response = client.get(url).send().await?;  // Envoie la requête HTTP
-    let file_name = url.split('/').last().unwrap_or("unknown");
+    fs::create_dir_all("downloads").expect("Failed to create downloads directory");
 
+    let url = entry.url.as_str();
+    let (file_name, named_by) = choose_file_name(client, url, config, Output::Silent, |e, delay, _| {
+        progress.eprintln(&format!("{}: {}. Retrying in {:.1}s...", url, e, delay.as_secs_f32()))
+    })
+    .await?;
     let save_path = format!("downloads/{}", file_name);
 
-    let mut file = File::create(&save_path).await?;     let mut stream = response.bytes_stream();
+    let reservation = match reserve_path(Path::new(&save_path), config.clobber_policy(false))? {
+        Some(reservation) => reservation,
+        None => {
+            progress.println(&format!("File '{}' already there; not retrieving.", save_path));
+            return Ok(save_path);
+        }
+    };
+    let save_path = reservation.path().to_string_lossy().into_owned();
+    let checksum = config.checksum_for(entry.checksum.as_ref(), &file_name)?;
+
+    let mut target = Target {
+        url,
+        save_path: &save_path,
+        position: if config.continue_download {
+            resume_position(&save_path).await
+        } else {
+            0
+        },
+        timestamping: config.timestamping,
+        checksum: checksum.as_ref(),
+        quarantine_dir: config.quarantine_dir.as_deref(),
+        request: &config.request,
+        read_timeout: config.timeouts.read,
+        pending: None,
+    };
+    target.pending = reusable(named_by, &target);
+
+    let bar = progress.start(&file_name);
+    let result = config
+        .retry
+        .run(
+            async || fetch_to_file(client, &mut target, Output::Silent, Some(&bar), None).await,
+            |e, delay, _| progress.eprintln(&format!("{}: {}. Retrying in {:.1}s...", url, e, delay.as_secs_f32())),
+        )
+        .await;
+
+    // La barre laisse place à une ligne de résumé
+    let summary = progress.finish(&bar);
+    result?;
+    progress.println(&format!("Finished downloading {} [{}]", save_path, summary));
+    Ok(save_path)
+}
 
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk?;
-        file.write_all(&chunk).await?;
+// Réponse prête à être écrite sur le disque, à partir de `offset`
+struct Transfer {
+    response: Response,
+    offset: u64,
+    total_size: u64,
+}
+
+// Vérification de la réponse avant d'écrire quoi que ce soit
+enum Prepared {
+    Transfer(Transfer),
+    AlreadyComplete,
+    // 416 sans que le serveur confirme une taille égale à la copie locale
+    ResumeRejected,
+    NotModified,
+}
+
+// Envoie la requête configurée, avec un en-tête Range si on reprend un téléchargement,
+// ou des en-têtes conditionnels en mode -N. Chaque redirection suivie est affichée.
+async fn send_request(client: &Client, target: &Target<'_>, output: Output) -> Result<Response, Box<dyn Error>> {
+    let mut request = target.request.build(client, target.url);
+    if target.position > 0 {
+        request = request.header(RANGE, format!("bytes={}-", target.position));
+    } else if target.timestamping {
+        request = request.headers(timestamp::conditional_headers(Path::new(target.save_path)));
     }
+    let mut on_redirect = |location: &Url| {
+        output.print(&format!("Location: {} [following]", location));
+        Ok(())
+    };
+    let response = target.request.send_following(request, &mut on_redirect);
+    idle(target.read_timeout, response).await?
+}
 
-    Ok(())
+// Vérifie la réponse du serveur et détermine où reprendre l'écriture
+fn prepare_transfer(response: Response, url: &str, offset: u64) -> Result<Prepared, Box<dyn Error>> {
+    let status = response.status();
+
+    // Le fichier n'est complet que si `Content-Range: bytes */TOTAL` annonce
+    // exactement la taille déjà reçue
+    if offset > 0 && status == StatusCode::RANGE_NOT_SATISFIABLE {
+        let total = response
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_unsatisfied_range);
+        return Ok(if total == Some(offset) {
+            Prepared::AlreadyComplete
+        } else {
+            Prepared::ResumeRejected
+        });
+    }
+    if status == StatusCode::NOT_MODIFIED {
+        return Ok(Prepared::NotModified);
+    }
+    if !status.is_success() {
+        return Err(Box::new(HttpStatusError::from_response(url, &response)));
+    }
+
+    if status == StatusCode::PARTIAL_CONTENT {
+        let (start, total) = response
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_content_range)
+            .ok_or_else(|| format!("Invalid Content-Range in partial response from {}", url))?;
+        if start != offset {
+            return Err(format!(
+                "Server resumed {} at byte {} instead of {}",
+                url, start, offset
+            )
+            .into());
+        }
+        let total_size = total.unwrap_or(offset + response.content_length().unwrap_or(0));
+        return Ok(Prepared::Transfer(Transfer { response, offset, total_size }));
+    }
+
+    // 200 : le serveur ignore le Range, on retélécharge tout
+    let total_size = response.content_length().unwrap_or(0);
+    Ok(Prepared::Transfer(Transfer { response, offset: 0, total_size }))
+}
+
+// Analyse un en-tête `Content-Range: bytes START-END/TOTAL` (TOTAL peut valoir `*`)
+pub fn parse_content_range(value: &str) -> Option<(u64, Option<u64>)> {
+    let range = value.trim().strip_prefix("bytes")?.trim_start();
+    let (span, total) = range.split_once('/')?;
+    let (start, _end) = span.split_once('-')?;
+    let start = start.trim().parse().ok()?;
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+    Some((start, total))
+}
+
+// Taille annoncée avec un 416 : `Content-Range: bytes */TOTAL`
+fn parse_unsatisfied_range(value: &str) -> Option<u64> {
+    value.trim().strip_prefix("bytes")?.trim_start().strip_prefix("*/")?.trim().parse().ok()
+}
+
+// Taille du fichier déjà présent sur le disque (0 s'il n'existe pas)
+async fn existing_len(path: &Path) -> u64 {
+    tokio::fs::metadata(path).await.map(|meta| meta.len()).unwrap_or(0)
+}
+
+// -c : on reprend le `.part` laissé par une tentative précédente, ou à défaut
+// un fichier partiel déjà présent sous son nom définitif
+async fn resume_position(save_path: &str) -> u64 {
+    let part = part_path(Path::new(save_path));
+    if part.exists() {
+        existing_len(&part).await
+    } else {
+        existing_len(Path::new(save_path)).await
+    }
+}
+
+// Ouvre le `.part` de destination : en ajout si on reprend, sinon en le tronquant
+async fn open_output(save_path: &str, offset: u64) -> std::io::Result<File> {
+    let part = part_path(Path::new(save_path));
+    if offset > 0 {
+        if !part.exists() {
+            tokio::fs::rename(save_path, &part).await?;
+        }
+        OpenOptions::new().append(true).open(&part).await
+    } else {
+        File::create(&part).await
+    }
 }
 
-fn parse_rate_limit(rate_limit: &str) -> Result<u64, String> {
-    let len = rate_limit.len();
-    if len == 0 {
-        return Err("Invalid rate limit format".to_string());
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "http://example.com/file";
+
+    fn response(status: u16, headers: &[(&str, &str)], body: &'static str) -> Response {
+        let mut builder = hyper::Response::builder().status(status);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        Response::from(builder.body(body).unwrap())
+    }
+
+    #[test]
+    fn content_range() {
+        assert_eq!(parse_content_range("bytes 100-199/1000"), Some((100, Some(1000))));
+        assert_eq!(parse_content_range("bytes 100-199/*"), Some((100, None)));
+        assert_eq!(parse_content_range("bytes */1000"), None);
+        assert_eq!(parse_content_range("items 0-1/2"), None);
+    }
+
+    #[test]
+    fn unsatisfied_range() {
+        assert_eq!(parse_unsatisfied_range("bytes */1000"), Some(1000));
+        assert_eq!(parse_unsatisfied_range("bytes 0-1/1000"), None);
+        assert_eq!(parse_unsatisfied_range("bytes */*"), None);
     }
-    let (num_part, unit) = rate_limit.split_at(len - 1);
-    let num: u64 = num_part.parse().map_err(|_| "Invalid number in rate limit".to_string())?;
-    match unit {
-        "k" | "K" => Ok(num * 1024),
-        "M" | "m" => Ok(num * 1024 * 1024),
-        _ => Err("Invalid unit in rate limit, use 'k' or 'M'".to_string()),
+
+    #[test]
+    fn partial_content_resumes_at_offset() {
+        let response = response(206, &[("content-range", "bytes 100-199/200")], "");
+        match prepare_transfer(response, URL, 100).unwrap() {
+            Prepared::Transfer(transfer) => {
+                assert_eq!(transfer.offset, 100);
+                assert_eq!(transfer.total_size, 200);
+            }
+            _ => panic!("expected a transfer"),
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn partial_content_at_wrong_start_is_rejected() {
+        let from_start = response(206, &[("content-range", "bytes 0-199/200")], "");
+        assert!(prepare_transfer(from_start, URL, 100).is_err());
+        // 206 sans Content-Range : impossible de savoir où reprendre
+        let no_range = response(206, &[], "");
+        assert!(prepare_transfer(no_range, URL, 100).is_err());
+    }
+
+    #[test]
+    fn full_content_restarts_from_zero() {
+        let response = response(200, &[("content-length", "5")], "hello");
+        match prepare_transfer(response, URL, 3).unwrap() {
+            Prepared::Transfer(transfer) => {
+                assert_eq!(transfer.offset, 0);
+                assert_eq!(transfer.total_size, 5);
+            }
+            _ => panic!("expected a transfer"),
+        }
+    }
+
+    #[test]
+    fn unsatisfiable_range_needs_a_matching_total() {
+        let complete = response(416, &[("content-range", "bytes */100")], "");
+        assert!(matches!(prepare_transfer(complete, URL, 100).unwrap(), Prepared::AlreadyComplete));
+        let longer = response(416, &[("content-range", "bytes */50")], "");
+        assert!(matches!(prepare_transfer(longer, URL, 100).unwrap(), Prepared::ResumeRejected));
+        let unknown = response(416, &[], "");
+        assert!(matches!(prepare_transfer(unknown, URL, 100).unwrap(), Prepared::ResumeRejected));
+    }
+
+    #[test]
+    fn unsatisfiable_range_without_resume_is_an_error() {
+        let response = response(416, &[("content-range", "bytes */100")], "");
+        assert!(prepare_transfer(response, URL, 0).is_err());
+    }
+}
@@ -0,0 +1,43 @@
+use url::Url;
+
+// L'hôte est le domaine lui-même ou l'un de ses sous-domaines, jamais une
+// simple sous-chaîne ("notexample.com" n'est pas dans "example.com")
+pub fn in_domain(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+// Hôte et port : deux ports d'un même nom d'hôte sont deux serveurs différents
+pub fn host_key(url: &Url) -> String {
+    format!(
+        "{}:{}",
+        url.host_str().unwrap_or_default().to_ascii_lowercase(),
+        url.port_or_known_default().unwrap_or_default()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn domain_and_subdomains() {
+        assert!(in_domain("example.com", "example.com"));
+        assert!(in_domain("www.example.com", "example.com"));
+        assert!(in_domain("a.b.example.com", "example.com"));
+        assert!(!in_domain("notexample.com", "example.com"));
+        assert!(!in_domain("example.com", "www.example.com"));
+        assert!(!in_domain("example.com.evil", "example.com"));
+    }
+
+    #[test]
+    fn host_key_includes_the_port() {
+        let key = |url: &str| host_key(&Url::parse(url).unwrap());
+        assert_eq!(key("https://Example.com/a"), "example.com:443");
+        assert_eq!(key("http://example.com/b"), "example.com:80");
+        assert_eq!(key("http://example.com:8080/"), "example.com:8080");
+        assert_eq!(key("http://[::1]:3128/"), "[::1]:3128");
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::{HashMap, VecDeque};
+use url::Url;
+
+use crate::hosts::host_key;
+
+// File d'attente de téléchargements : au plus `jobs` actifs en même temps, et au
+// plus `per_host` vers un même serveur. Les entrées démarrent dans l'ordre
+// d'arrivée ; une entrée dont le serveur est saturé attend sans bloquer celles
+// des autres serveurs.
+#[derive(Debug)]
+pub struct JobQueue<T> {
+    pending: VecDeque<(String, T)>,
+    active: HashMap<String, usize>,
+    running: usize,
+    jobs: usize,
+    per_host: Option<usize>,
+}
+
+impl<T> JobQueue<T> {
+    pub fn new(jobs: usize, per_host: Option<usize>) -> Self {
+        JobQueue {
+            pending: VecDeque::new(),
+            active: HashMap::new(),
+            running: 0,
+            jobs: jobs.max(1),
+            per_host: per_host.map(|limit| limit.max(1)),
+        }
+    }
+
+    pub fn push(&mut self, url: &str, job: T) {
+        self.pending.push_back((queue_key(url), job));
+    }
+
+    // Prochaine entrée pouvant démarrer, avec la clé à rendre à `finish`
+    pub fn next_ready(&mut self) -> Option<(String, T)> {
+        if self.running >= self.jobs {
+            return None;
+        }
+        let index = self.pending.iter().position(|(host, _)| match self.per_host {
+            Some(limit) => self.active.get(host).copied().unwrap_or(0) < limit,
+            None => true,
+        })?;
+        let (host, job) = self.pending.remove(index)?;
+        *self.active.entry(host.clone()).or_insert(0) += 1;
+        self.running += 1;
+        Some((host, job))
+    }
+
+    // Libère la place occupée par une entrée terminée
+    pub fn finish(&mut self, host: &str) {
+        if let Some(count) = self.active.get_mut(host) {
+            *count -= 1;
+            if *count == 0 {
+                self.active.remove(host);
+            }
+        }
+        self.running -= 1;
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+// Hôte et port ; les URL invalides partagent une même clé (elles échoueront vite)
+fn queue_key(url: &str) -> String {
+    Url::parse(url).map(|url| host_key(&url)).unwrap_or_default()
+}
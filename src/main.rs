@@ -1,23 +1,51 @@
+mod auth;
+mod checksum;
+mod client;
+mod config;
+mod cookies;
 mod download;
+mod hosts;
+mod jobs;
 mod mirror;
+mod naming;
+mod pacing;
+mod progress;
+mod proxy;
+mod rate_limiter;
+mod redirect;
+mod report;
+mod request;
+mod retry;
+mod robots;
+mod segments;
+mod timeouts;
+mod timestamp;
+mod tls;
 mod utils;
 
 use chrono::Local;
 use clap::{Arg, Command};
+use config::DownloadConfig;
+use report::ExitCode;
 #[tokio::main(flavor = "multi_thread", worker_threads = 4)]
 async fn main() {
     let matches = Command::new("Rust-Wget")
         .version("1.0")
         .about("Recreate wget functionality in Rust")
+        .after_help(
+            "Exit status: 0 success, 1 generic error, 2 parse error, 3 I/O error, 4 network failure, \
+             5 SSL verification failure, 6 authentication failure, 7 protocol error, \
+             8 server error response, 9 checksum mismatch",
+        )
         .arg(
             Arg::new("url")
                 .help("URL to download")
-                .required_unless_present_any(&["input", "mirror"]), // Téléchargement d'un fichier requis sauf si 'input' ou 'mirror' est présent
+                .required_unless_present_any(["input", "mirror"]), // Téléchargement d'un fichier requis sauf si 'input' ou 'mirror' est présent
         )
         .arg(
             Arg::new("output")
                 .short('O')
-                .help("Save the file with a specific name"),
+                .help("Save the file with a specific name ('-' for standard output)"),
         )
         .arg(
             Arg::new("directory")
@@ -38,7 +66,28 @@ async fn main() {
         .arg(
             Arg::new("input")
                 .short('i')
-                .help("Download multiple files from a list"),
+                .help("Download multiple files from a list ('-' to read URLs from standard input)"),
+        )
+        .arg(
+            Arg::new("jobs")
+                .short('j')
+                .long("jobs")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Download at most N files of the -i list at the same time (default 4)"),
+        )
+        .arg(
+            Arg::new("max_per_host")
+                .long("max-per-host")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Open at most N simultaneous downloads to the same server (-i and --mirror)"),
+        )
+        .arg(
+            Arg::new("report_json")
+                .long("report-json")
+                .value_name("FILE")
+                .help("Write a JSON report of every URL (status, bytes, duration, path) to FILE"),
         )
         .arg(
             Arg::new("mirror")
@@ -66,6 +115,394 @@ async fn main() {
                 .action(clap::ArgAction::SetTrue)
                 .help("Convert links for offline viewing (used with --mirror)"),
         )
+        .arg(
+            Arg::new("span_hosts")
+                .short('H')
+                .long("span-hosts")
+                .action(clap::ArgAction::SetTrue)
+                .help("Follow links and fetch resources on other hosts with --mirror"),
+        )
+        .arg(
+            Arg::new("span_requisites")
+                .long("span-requisites")
+                .action(clap::ArgAction::SetTrue)
+                .help("Fetch images and stylesheets from other hosts (e.g. a CDN) without following their pages"),
+        )
+        .arg(
+            Arg::new("domains")
+                .short('D')
+                .long("domains")
+                .value_name("LIST")
+                .help("Comma-separated domains that -H and --span-requisites may reach"),
+        )
+        .arg(
+            Arg::new("exclude_domains")
+                .long("exclude-domains")
+                .value_name("LIST")
+                .help("Comma-separated domains never visited with --mirror"),
+        )
+        .arg(
+            Arg::new("level")
+                .short('l')
+                .long("level")
+                .value_name("N")
+                .help("Maximum link depth for --mirror (0 or 'inf' for unlimited, the default)"),
+        )
+        .arg(
+            Arg::new("page_requisites_depth")
+                .long("page-requisites-depth")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Fetch images and stylesheets up to N levels beyond --level (default 1)"),
+        )
+        .arg(
+            Arg::new("mirror_jobs")
+                .long("mirror-jobs")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Fetch at most N pages and resources at the same time with --mirror (default 4)"),
+        )
+        .arg(
+            Arg::new("wait")
+                .short('w')
+                .long("wait")
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(f64))
+                .help("Wait SECS seconds between requests to the same server (-i and --mirror)"),
+        )
+        .arg(
+            Arg::new("random_wait")
+                .long("random-wait")
+                .action(clap::ArgAction::SetTrue)
+                .help("Vary the --wait delay between 0.5 and 1.5 times its value"),
+        )
+        .arg(
+            Arg::new("rate_per_host")
+                .long("rate-per-host")
+                .value_name("N")
+                .value_parser(clap::value_parser!(f64))
+                .help("Send at most N requests per second to the same server (-i and --mirror)"),
+        )
+        .arg(
+            Arg::new("execute")
+                .short('e')
+                .long("execute")
+                .value_name("COMMAND")
+                .action(clap::ArgAction::Append)
+                .help("Run a .wgetrc-style command, e.g. 'robots=off' to ignore robots.txt with --mirror"),
+        )
+        .arg(
+            Arg::new("continue")
+                .short('c')
+                .long("continue")
+                .action(clap::ArgAction::SetTrue)
+                .help("Resume a partially downloaded file"),
+        )
+        .arg(
+            Arg::new("tries")
+                .short('t')
+                .long("tries")
+                .value_name("N")
+                .help("Set number of tries (0 or 'inf' for unlimited)"),
+        )
+        .arg(
+            Arg::new("waitretry")
+                .long("waitretry")
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(u64))
+                .help("Wait at most SECS seconds between retries (default 10; a Retry-After is honored up to 300)"),
+        )
+        .arg(
+            Arg::new("retry_on_http_error")
+                .long("retry-on-http-error")
+                .value_name("CODES")
+                .help("Comma-separated HTTP status codes to retry, e.g. 503,429"),
+        )
+        .arg(
+            Arg::new("segments")
+                .long("segments")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Download a single file over N parallel connections"),
+        )
+        .arg(
+            Arg::new("timestamping")
+                .short('N')
+                .long("timestamping")
+                .action(clap::ArgAction::SetTrue)
+                .help("Don't re-retrieve files unless newer than the local copy"),
+        )
+        .arg(
+            Arg::new("content_disposition")
+                .long("content-disposition")
+                .action(clap::ArgAction::SetTrue)
+                .help("Name the file after the Content-Disposition header"),
+        )
+        .arg(
+            Arg::new("trust_server_names")
+                .long("trust-server-names")
+                .action(clap::ArgAction::SetTrue)
+                .help("Name the file after the final URL of a redirection"),
+        )
+        .arg(
+            Arg::new("no_clobber")
+                .long("no-clobber")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("backups")
+                .help("Skip downloads that would overwrite existing files"),
+        )
+        .arg(
+            Arg::new("backups")
+                .long("backups")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u32))
+                .help("Keep up to N numbered backups of overwritten files"),
+        )
+        .arg(
+            Arg::new("checksum")
+                .long("checksum")
+                .value_name("ALGO:HEX")
+                .help("Verify the downloaded file (sha256, sha512, sha1, md5 or blake3)"),
+        )
+        .arg(
+            Arg::new("checksum_file")
+                .long("checksum-file")
+                .value_name("FILE")
+                .help("Look up expected checksums by file name, e.g. in SHA256SUMS"),
+        )
+        .arg(
+            Arg::new("quarantine_dir")
+                .long("quarantine-dir")
+                .value_name("DIR")
+                .help("Move files failing checksum verification to DIR instead of deleting them"),
+        )
+        .arg(
+            Arg::new("header")
+                .long("header")
+                .value_name("HEADER")
+                .action(clap::ArgAction::Append)
+                .help("Send an extra 'Name: value' header (repeatable)"),
+        )
+        .arg(
+            Arg::new("user_agent")
+                .short('U')
+                .long("user-agent")
+                .value_name("AGENT")
+                .help("Identify as AGENT instead of the default browser User-Agent"),
+        )
+        .arg(
+            Arg::new("referer")
+                .long("referer")
+                .value_name("URL")
+                .help("Send a Referer header"),
+        )
+        .arg(
+            Arg::new("method")
+                .long("method")
+                .value_name("METHOD")
+                .help("Use METHOD instead of GET (e.g. POST, PUT)"),
+        )
+        .arg(
+            Arg::new("post_data")
+                .long("post-data")
+                .value_name("STRING")
+                .conflicts_with_all(["post_file", "body_file"])
+                .help("POST STRING as form data"),
+        )
+        .arg(
+            Arg::new("post_file")
+                .long("post-file")
+                .value_name("FILE")
+                .conflicts_with("body_file")
+                .help("POST the contents of FILE as form data"),
+        )
+        .arg(
+            Arg::new("body_file")
+                .long("body-file")
+                .value_name("FILE")
+                .requires("method")
+                .help("Send the contents of FILE as the request body (with --method)"),
+        )
+        .arg(
+            Arg::new("user")
+                .long("user")
+                .visible_alias("http-user")
+                .value_name("USER")
+                .help("Authenticate as USER (Basic or Digest)"),
+        )
+        .arg(
+            Arg::new("password")
+                .long("password")
+                .visible_alias("http-password")
+                .value_name("PASS")
+                .requires("user")
+                .help("Password for --user"),
+        )
+        .arg(
+            Arg::new("ask_password")
+                .long("ask-password")
+                .action(clap::ArgAction::SetTrue)
+                .requires("user")
+                .conflicts_with("password")
+                .help("Prompt for the password of --user"),
+        )
+        .arg(
+            Arg::new("bearer_token")
+                .long("bearer-token")
+                .value_name("TOKEN")
+                .conflicts_with("user")
+                .help("Send an 'Authorization: Bearer TOKEN' header"),
+        )
+        .arg(
+            Arg::new("load_cookies")
+                .long("load-cookies")
+                .value_name("FILE")
+                .help("Load cookies from FILE (Netscape cookies.txt format)"),
+        )
+        .arg(
+            Arg::new("save_cookies")
+                .long("save-cookies")
+                .value_name("FILE")
+                .help("Save cookies to FILE at the end of the run"),
+        )
+        .arg(
+            Arg::new("keep_session_cookies")
+                .long("keep-session-cookies")
+                .action(clap::ArgAction::SetTrue)
+                .help("Also save session cookies with --save-cookies"),
+        )
+        .arg(
+            Arg::new("proxy")
+                .long("proxy")
+                .value_name("URL")
+                .help("Use proxy URL (http://, socks5:// or socks5h://) instead of the environment"),
+        )
+        .arg(
+            Arg::new("no_proxy")
+                .long("no-proxy")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("proxy")
+                .help("Don't use any proxy, even if set in the environment"),
+        )
+        .arg(
+            Arg::new("proxy_user")
+                .long("proxy-user")
+                .value_name("USER")
+                .help("Authenticate to the proxy as USER"),
+        )
+        .arg(
+            Arg::new("proxy_password")
+                .long("proxy-password")
+                .value_name("PASS")
+                .requires("proxy_user")
+                .help("Password for --proxy-user"),
+        )
+        .arg(
+            Arg::new("ca_certificate")
+                .long("ca-certificate")
+                .value_name("FILE")
+                .help("Trust the certificate authorities in FILE (PEM bundle or DER)"),
+        )
+        .arg(
+            Arg::new("ca_directory")
+                .long("ca-directory")
+                .value_name("DIR")
+                .help("Trust the certificate authorities found in DIR"),
+        )
+        .arg(
+            Arg::new("certificate")
+                .long("certificate")
+                .value_name("FILE")
+                .help("Client certificate for mutual TLS (PEM or PKCS#12)"),
+        )
+        .arg(
+            Arg::new("certificate_type")
+                .long("certificate-type")
+                .value_name("TYPE")
+                .requires("certificate")
+                .help("Format of --certificate: PEM or P12 (guessed from the extension)"),
+        )
+        .arg(
+            Arg::new("certificate_password")
+                .long("certificate-password")
+                .value_name("PASS")
+                .requires("certificate")
+                .help("Password of a PKCS#12 client certificate"),
+        )
+        .arg(
+            Arg::new("private_key")
+                .long("private-key")
+                .value_name("FILE")
+                .requires("certificate")
+                .help("PKCS#8 PEM private key of the client certificate"),
+        )
+        .arg(
+            Arg::new("pinned_pubkey")
+                .long("pinnedpubkey")
+                .value_name("HASHES|FILE")
+                .help("Only accept servers whose public key matches sha256//BASE64[;...] or FILE"),
+        )
+        .arg(
+            Arg::new("min_tls_version")
+                .long("min-tls-version")
+                .value_name("VERSION")
+                .help("Minimum TLS version: 1.0, 1.1 or 1.2"),
+        )
+        .arg(
+            Arg::new("no_check_certificate")
+                .long("no-check-certificate")
+                .action(clap::ArgAction::SetTrue)
+                .help("Don't verify the server certificate"),
+        )
+        .arg(
+            Arg::new("timeout")
+                .short('T')
+                .long("timeout")
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(f64))
+                .help("Set the connect, DNS and read timeouts to SECS (0 disables them)"),
+        )
+        .arg(
+            Arg::new("connect_timeout")
+                .long("connect-timeout")
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(f64))
+                .help("Give up connecting after SECS seconds"),
+        )
+        .arg(
+            Arg::new("dns_timeout")
+                .long("dns-timeout")
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(f64))
+                .help("Give up resolving a host name after SECS seconds"),
+        )
+        .arg(
+            Arg::new("read_timeout")
+                .long("read-timeout")
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(f64))
+                .help("Retry when no data arrives for SECS seconds (default 900)"),
+        )
+        .arg(
+            Arg::new("max_redirect")
+                .long("max-redirect")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Follow at most N redirections (default 20)"),
+        )
+        .arg(
+            Arg::new("no_redirect")
+                .long("no-redirect")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("max_redirect")
+                .help("Don't follow redirections"),
+        )
+        .arg(
+            Arg::new("allow_https_downgrade")
+                .long("allow-https-downgrade")
+                .action(clap::ArgAction::SetTrue)
+                .help("Follow redirections from HTTPS to plain HTTP"),
+        )
         .arg(
             Arg::new("already_in_background")
                 .long("already_in_background")
@@ -75,10 +512,32 @@ async fn main() {
         )
         .get_matches();
 
+    let config = match DownloadConfig::from_matches(&matches) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(ExitCode::Parse as i32);
+        }
+    };
+
+    // Un seul client pour toute l'exécution : les connexions sont réutilisées
+    let client = match client::build_client(&config) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Cannot initialize the HTTP client: {}", e);
+            std::process::exit(ExitCode::Parse as i32);
+        }
+    };
+
+    // Code de sortie compatible avec wget : chaque URL inscrit son résultat au
+    // bilan, les erreurs globales (fichier -i illisible...) s'y ajoutent
+    let mut exit_code = ExitCode::Success;
+
     // Téléchargement de fichiers multiples
     if let Some(file_path) = matches.get_one::<String>("input") {
-        if let Err(e) = download::download_multiple_files(file_path).await {
+        if let Err(e) = download::download_multiple_files(&client, file_path, &config).await {
             eprintln!("Error occurred during multiple file download: {}", e);
+            exit_code = exit_code.combine(ExitCode::for_error(e.as_ref()));
         }
     // Mirroring d'un site complet avec gestion des flags --mirror, --reject, --exclude, --convert-links
     } else if matches.get_flag("mirror") {
@@ -90,13 +549,17 @@ async fn main() {
         // Appel à la fonction `mirror_website` pour effectuer le mirroring
         println!("Mirroring website: {}", mirror_url);
 
+        // Chaque page et ressource est inscrite au bilan par le miroir lui-même
         if let Err(e) = mirror::mirror_website(
+            &client,
             mirror_url,
-            reject_types.map(|s| &**s),   // Handle file types to reject
-            exclude_dirs.map(|s| &**s),   // Handle directories to exclude
+            reject_types.map(|s| s.as_str()),   // Handle file types to reject
+            exclude_dirs.map(|s| s.as_str()),   // Handle directories to exclude
             convert_links,                // Handle link conversion for offline viewing
+            &config,
         ).await {
             eprintln!("Error occurred during website mirroring: {}", e);
+            exit_code = exit_code.combine(ExitCode::for_error(e.as_ref()));
         }
 
     // Téléchargement d'un seul fichier
@@ -110,19 +573,11 @@ async fn main() {
         if background && !already_in_background {
             println!("Output will be written to 'wget-log'.");
 
-            // Commande pour exécuter en arrière-plan
+            // Commande pour exécuter en arrière-plan, avec les mêmes arguments
             let mut command = std::process::Command::new(std::env::current_exe().unwrap());
-            command.arg("--already_in_background").arg(url);
-
-            if let Some(ref name) = file_name {
-                command.arg("-O").arg(name);
-            }
-            if let Some(ref dir) = directory {
-                command.arg("-P").arg(dir);
-            }
-            if let Some(ref rate) = rate_limit {
-                command.arg("--rate-limit").arg(rate);
-            }
+            command
+                .arg("--already_in_background")
+                .args(std::env::args().skip(1));
 
             // Redirection des logs vers `wget-log` explicitement
             let log_file = std::fs::OpenOptions::new()
@@ -134,6 +589,8 @@ async fn main() {
             command.stdout(log_file.try_clone().unwrap()); // Redirection stdout vers fichier
             command.stderr(log_file); // Redirection stderr vers fichier
 
+            // Le processus fils est volontairement détaché du terminal
+            #[allow(clippy::zombie_processes)]
             command.spawn().expect("Failed to launch background process");
 
             return; // Libérer immédiatement le terminal
@@ -141,11 +598,13 @@ async fn main() {
 
         // Télécharger en mode normal ou si déjà en arrière-plan
         if let Err(e) = download::download_single_file(
+            &client,
             url,
             file_name.as_deref(),
             directory.as_deref(),
             rate_limit.as_deref(),
             background,
+            &config,
         )
         .await
         {
@@ -153,6 +612,28 @@ async fn main() {
         }
     }
 
+    if let Err(e) = config.save_cookies() {
+        eprintln!("Cannot save cookies: {}", e);
+        exit_code = exit_code.combine(ExitCode::Io);
+    }
+
+    let exit_code = exit_code.combine(config.report.exit_code());
+    if let Some(path) = &config.report_json {
+        if let Err(e) = config.report.write_json(path, exit_code) {
+            eprintln!("Cannot write report to {}: {}", path.display(), e);
+        }
+    }
+
     let end_time = Local::now();
-    println!("Finished at: {}", end_time.format("%Y-%m-%d %H:%M:%S"));
+    let finished = format!("Finished at: {}", end_time.format("%Y-%m-%d %H:%M:%S"));
+    // Avec -O -, rien d'autre que le contenu ne doit arriver sur la sortie standard
+    if matches.get_one::<String>("output").is_some_and(|output| output == "-") {
+        eprintln!("{}", finished);
+    } else {
+        println!("{}", finished);
+    }
+
+    if exit_code != ExitCode::Success {
+        std::process::exit(exit_code as i32);
+    }
 }
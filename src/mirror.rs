@@ -1,232 +1,769 @@
+use clap::ArgMatches;
+use futures::stream::StreamExt;
 use regex::Regex;
-use reqwest::Client;
+use reqwest::header::HeaderMap;
+use reqwest::{Client, StatusCode};
 use scraper::{Html, Selector};
-use std::collections::{HashSet, VecDeque};
+use futures::stream::FuturesUnordered;
+use std::collections::{HashMap, HashSet};
 use std::fs::create_dir_all;
-use std::path::Path;
-use tokio::fs::{self, File};
-use tokio::io::AsyncWriteExt;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::time::Instant;
 use url::Url;
-use std::sync::{Arc, Mutex};
+
+use crate::config::DownloadConfig;
+use crate::hosts::in_domain;
+use crate::jobs::JobQueue;
+use crate::redirect::{same_host, RedirectError};
+use crate::report::Entry;
+use crate::request::RequestOptions;
+use crate::retry::HttpStatusError;
+use crate::robots::RobotsCache;
+use crate::timeouts::idle;
+use crate::timestamp;
+use crate::utils::write_atomically;
+
+// Profondeur, parallélisme, politesse et hôtes du miroir (-l/--level,
+// --page-requisites-depth, --mirror-jobs, -e robots=off, -H, -D, --exclude-domains)
+#[derive(Clone, Debug)]
+pub struct MirrorOptions {
+    // Profondeur maximale des pages, `None` pour illimitée
+    pub level: Option<usize>,
+    // Niveaux supplémentaires accordés aux ressources d'une page : avec 1, les
+    // pages les plus profondes gardent leurs images et feuilles de style
+    pub requisites_depth: usize,
+    // Requêtes simultanées (--mirror-jobs)
+    pub jobs: usize,
+    // Respect de robots.txt et des balises meta robots (désactivé par -e robots=off)
+    pub robots: bool,
+    // Sortie de l'hôte de départ : pour tout (-H) ou pour les seules ressources
+    // des pages (--span-requisites), limitée aux domaines de -D s'il y en a
+    pub span_hosts: bool,
+    pub span_requisites: bool,
+    pub domains: Vec<String>,
+    // Domaines jamais visités, hôte de départ compris
+    pub exclude_domains: Vec<String>,
+}
+
+impl Default for MirrorOptions {
+    fn default() -> Self {
+        MirrorOptions {
+            level: None,
+            requisites_depth: 1,
+            jobs: 4,
+            robots: true,
+            span_hosts: false,
+            span_requisites: false,
+            domains: Vec::new(),
+            exclude_domains: Vec::new(),
+        }
+    }
+}
+
+impl MirrorOptions {
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, String> {
+        let level = match matches.get_one::<String>("level") {
+            Some(value) => parse_level(value)?,
+            None => None,
+        };
+
+        // -e commande=valeur, comme dans un `.wgetrc`
+        let mut robots = true;
+        for command in matches.get_many::<String>("execute").into_iter().flatten() {
+            let (name, value) = command
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid command '{}', expected name=value", command))?;
+            match name.trim().to_ascii_lowercase().as_str() {
+                "robots" => robots = parse_switch(value)?,
+                other => return Err(format!("Unknown command '{}'", other)),
+            }
+        }
+
+        Ok(MirrorOptions {
+            level,
+            robots,
+            requisites_depth: matches
+                .get_one::<usize>("page_requisites_depth")
+                .copied()
+                .unwrap_or(1),
+            jobs: matches.get_one::<usize>("mirror_jobs").copied().unwrap_or(4),
+            span_hosts: matches.get_flag("span_hosts"),
+            span_requisites: matches.get_flag("span_requisites"),
+            domains: parse_domains(matches.get_one::<String>("domains")),
+            exclude_domains: parse_domains(matches.get_one::<String>("exclude_domains")),
+        })
+    }
+
+    // Les liens d'une page à la profondeur `depth` mènent à `depth + 1`
+    pub fn follows_links(&self, depth: usize) -> bool {
+        self.level.is_none_or(|level| depth < level)
+    }
+
+    pub fn fetches_requisites(&self, depth: usize) -> bool {
+        self.level.is_none_or(|level| depth < level + self.requisites_depth)
+    }
+
+    // Comme wget, -D ne fait que restreindre les hôtes atteints grâce à -H (ou
+    // --span-requisites) ; `requisite` distingue images et feuilles de style des pages
+    pub fn accepts(&self, start: &Url, url: &Url, requisite: bool) -> bool {
+        if !matches!(url.scheme(), "http" | "https") {
+            return false;
+        }
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        if self.exclude_domains.iter().any(|domain| in_domain(host, domain)) {
+            return false;
+        }
+        if same_host(start, url) {
+            return true;
+        }
+        let spanning = self.span_hosts || (requisite && self.span_requisites);
+        spanning && (self.domains.is_empty() || self.domains.iter().any(|domain| in_domain(host, domain)))
+    }
+}
+
+// Liste séparée par des virgules ; un point initial (".example.com") est toléré
+fn parse_domains(value: Option<&String>) -> Vec<String> {
+    value
+        .map(|value| {
+            value
+                .split(',')
+                .map(|domain| domain.trim().trim_start_matches('.').to_ascii_lowercase())
+                .filter(|domain| !domain.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// on/off, comme les booléens d'un `.wgetrc`
+fn parse_switch(value: &str) -> Result<bool, String> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "on" | "yes" | "1" | "true" => Ok(true),
+        "off" | "no" | "0" | "false" => Ok(false),
+        _ => Err(format!("Invalid value '{}', expected on or off", value)),
+    }
+}
+
+// "inf" ou 0 : profondeur illimitée, comme wget
+fn parse_level(value: &str) -> Result<Option<usize>, String> {
+    if value.eq_ignore_ascii_case("inf") {
+        return Ok(None);
+    }
+    match value.parse::<usize>() {
+        Ok(0) => Ok(None),
+        Ok(level) => Ok(Some(level)),
+        Err(_) => Err(format!("Invalid recursion level: {}", value)),
+    }
+}
 
 // Fonction pour le mirroring récursif d'un site
 pub async fn mirror_website(
+    client: &Client,
     url: &str,
     reject_types: Option<&str>,
     exclude_dirs: Option<&str>,
     convert_links: bool,
+    config: &DownloadConfig,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let visited_urls = Arc::new(Mutex::new(HashSet::new()));
-
-    async fn mirror_recursive(
-        url: &str,
-        reject_types: Option<&str>,
-        exclude_dirs: Option<&str>,
-        convert_links: bool,
-        visited_urls: Arc<Mutex<HashSet<String>>>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut visited = visited_urls.lock().unwrap();
-        if visited.contains(url) {
-            println!("Skipping already visited URL: {}", url);
-            return Ok(());
+    let start = Url::parse(url)?;
+    // Les identifiants donnés en ligne de commande restent réservés à l'hôte de
+    // départ, même pour les pages et ressources des hôtes atteints avec -H
+    let config = &DownloadConfig {
+        request: RequestOptions {
+            credentials_origin: Some(start.clone()),
+            ..config.request.clone()
+        },
+        ..config.clone()
+    };
+    let crawl = Crawl {
+        client,
+        start,
+        reject_types,
+        exclude_dirs,
+        convert_links,
+        config,
+        robots: config.mirror.robots.then(RobotsCache::default),
+    };
+    let crawl = &crawl;
+
+    // File d'attente commune aux pages et aux ressources : au plus --mirror-jobs
+    // requêtes simultanées (et --max-per-host par serveur), démarrées dans l'ordre
+    // de découverte, ce qui garde un parcours en largeur
+    let mut queue = JobQueue::new(config.mirror.jobs, config.max_per_host);
+    let mut running = FuturesUnordered::new();
+    // Pages vues (fichier local et profondeur), ressources déjà programmées. Les
+    // clés sont les fichiers écrits : `http://h/a` et `http://h/a/` donnent tous deux
+    // `h/a/index.html`, et deux écritures simultanées s'écraseraient.
+    let mut seen_pages = HashMap::new();
+    let mut seen_files = HashSet::new();
+
+    let start = normalize_url(url)?;
+    seen_pages.insert(file_key(&page_file_path(&start)), 0);
+    queue.push(&start, Job::Page { url: start.clone(), depth: 0 });
+
+    loop {
+        while let Some((host, job)) = queue.next_ready() {
+            running.push(async move { (host, crawl.run(job).await) });
         }
-        visited.insert(url.to_string());
-        drop(visited);
-
-        let client = Client::new();
-        let response = client.get(url).send().await?;
-
-        if response.status().is_success() {
-            let html = response.text().await?;
-            let document = Html::parse_document(&html);
-
-            let domain = url.split("://").nth(1).unwrap().split('/').next().unwrap();
-            let relative_url = url.trim_start_matches("http://").trim_start_matches("https://");
-            let dir_name = format!("./{}", relative_url);
-            create_dir_all(&dir_name)?;
-
-            let html_file_path = format!("{}/index.html", &dir_name);
-            let mut file = File::create(&html_file_path).await?;
-            let mut modified_html = html.clone();
-
-            let link_selector = Selector::parse("a[href], img[src], link[href]").unwrap();
-            let reject_types_set = if let Some(types) = reject_types {
-                types.split(',').collect::<HashSet<&str>>()
-            } else {
-                HashSet::new()
+        let Some((host, discovered)) = running.next().await else {
+            break;
+        };
+        queue.finish(&host);
+
+        // Seul l'échec de la page de départ remonte jusqu'ici (voir `Crawl::run`)
+        for job in discovered? {
+            let new = match &job {
+                Job::Page { url, depth } => {
+                    let key = file_key(&page_file_path(url));
+                    let new = should_visit(&mut seen_pages, key, *depth, &config.mirror);
+                    if !new {
+                        println!("Skipping already visited URL: {}", url);
+                    }
+                    new
+                }
+                Job::Resource { save_path, .. } => seen_files.insert(file_key(save_path)),
             };
+            if new {
+                let url = job.url().to_string();
+                queue.push(&url, job);
+            }
+        }
+    }
+    Ok(())
+}
 
-            let exclude_dirs_set = if let Some(dirs) = exclude_dirs {
-                dirs.split(',').collect::<HashSet<&str>>()
-            } else {
-                HashSet::new()
-            };
+// Une requête du miroir : une page à analyser, ou une ressource à enregistrer
+enum Job {
+    Page { url: String, depth: usize },
+    Resource { url: String, save_path: String },
+}
 
-            let base_url = Url::parse(url)?;
-            let mut subpages_to_visit: VecDeque<String> = VecDeque::new();
+impl Job {
+    fn url(&self) -> &str {
+        match self {
+            Job::Page { url, .. } | Job::Resource { url, .. } => url,
+        }
+    }
+}
 
-            // Remplacement des liens dans les balises a, img, link
-            for element in document.select(&link_selector) {
-                let attr = match element.value().name() {
-                    "img" => element.value().attr("src"),
-                    "a" => element.value().attr("href"),
-                    "link" => element.value().attr("href"),
-                    _ => None,
-                };
+// Une page déjà vue n'est reprise que si un chemin plus court la rend plus
+// féconde (liens suivis ou ressources récupérées au lieu d'être ignorés)
+fn should_visit(seen: &mut HashMap<PathBuf, usize>, key: PathBuf, depth: usize, options: &MirrorOptions) -> bool {
+    if let Some(&previous) = seen.get(&key) {
+        let deeper_reach = (options.follows_links(depth) && !options.follows_links(previous))
+            || (options.fetches_requisites(depth) && !options.fetches_requisites(previous));
+        if depth >= previous || !deeper_reach {
+            return false;
+        }
+    }
+    seen.insert(key, depth);
+    true
+}
+
+// Fichier où `Crawl::page` enregistre la page `url`
+fn page_file_path(url: &str) -> String {
+    let relative_url = url.trim_start_matches("http://").trim_start_matches("https://");
+    format!("./{}/index.html", relative_url)
+}
+
+// Clé de déduplication d'un fichier local : `a//b` et `a/b` sont le même fichier
+fn file_key(path: &str) -> PathBuf {
+    Path::new(path).components().collect()
+}
+
+// Clé de déduplication : le crate `url` met déjà en minuscules le schéma et
+// l'hôte, retire le port par défaut et résout `.`/`..` ; le fragment ne désigne
+// pas une autre page
+fn normalize_url(url: &str) -> Result<String, url::ParseError> {
+    Ok(page_key(Url::parse(url)?))
+}
+
+fn page_key(mut url: Url) -> String {
+    url.set_fragment(None);
+    url.into()
+}
+
+// Cible d'un lien, absolu ou relatif à la page ; un lien invalide est ignoré
+// sans interrompre l'analyse de la page
+fn resolve_link(base_url: &Url, link: &str) -> Option<Url> {
+    match Url::parse(link).or_else(|_| base_url.join(link)) {
+        Ok(url) => Some(url),
+        Err(e) => {
+            println!("Skipping invalid link: {} ({})", link, e);
+            None
+        }
+    }
+}
+
+// Paramètres communs à toutes les requêtes du miroir
+struct Crawl<'a> {
+    client: &'a Client,
+    // Page de départ, dont l'hôte délimite le miroir (voir `MirrorOptions::accepts`)
+    start: Url,
+    reject_types: Option<&'a str>,
+    exclude_dirs: Option<&'a str>,
+    convert_links: bool,
+    config: &'a DownloadConfig,
+    // `None` avec -e robots=off
+    robots: Option<RobotsCache>,
+}
+
+impl Crawl<'_> {
+    // Exécute une requête, l'inscrit au bilan et renvoie les requêtes découvertes.
+    // Seul l'échec de la page de départ interrompt le miroir : les autres sont
+    // signalés et le parcours continue.
+    async fn run(&self, job: Job) -> Result<Vec<Job>, Box<dyn std::error::Error>> {
+        let url = Url::parse(job.url())?;
+        let crawl_delay = match &self.robots {
+            Some(robots) => match robots.permits(self.client, &url, self.config).await {
+                Ok(crawl_delay) => crawl_delay,
+                Err(()) => {
+                    println!("Rejected by robots.txt: {}", job.url());
+                    return Ok(Vec::new());
+                }
+            },
+            None => None,
+        };
+        // Pages et ressources d'un même hôte partagent le même rythme
+        self.config
+            .pacing
+            .wait_turn(&url.origin().ascii_serialization(), crawl_delay)
+            .await;
+
+        let started = Instant::now();
+        let (result, discovered) = match &job {
+            Job::Page { url, depth } => match self.page(url, *depth).await {
+                Ok((path, discovered)) => (Ok(path), discovered),
+                Err(e) => (Err(e), Vec::new()),
+            },
+            Job::Resource { url, save_path } => (
+                download_resource(self.client, url, save_path, self.config, &self.start).await,
+                Vec::new(),
+            ),
+        };
+        self.config
+            .report
+            .record(Entry::new(job.url(), &result, started.elapsed()));
+        match result {
+            Err(e) if matches!(job, Job::Page { depth: 0, .. }) => Err(e),
+            // Ces erreurs citent déjà l'URL
+            Err(e) if e.is::<HttpStatusError>() || e.is::<RedirectError>() => {
+                eprintln!("{}", e);
+                Ok(Vec::new())
+            }
+            Err(e) => {
+                eprintln!("Error downloading {}: {}", job.url(), e);
+                Ok(Vec::new())
+            }
+            Ok(_) => Ok(discovered),
+        }
+    }
+
+    // Enregistre une page et renvoie le fichier écrit (`None` avec noindex), ses
+    // sous-pages et ses ressources
+    async fn page(
+        &self,
+        url: &str,
+        depth: usize,
+    ) -> Result<(Option<String>, Vec<Job>), Box<dyn std::error::Error>> {
+        let (config, convert_links) = (self.config, self.convert_links);
+        let domain = url.split("://").nth(1).unwrap().split('/').next().unwrap();
+        let relative_url = url.trim_start_matches("http://").trim_start_matches("https://");
+        let dir_name = format!("./{}", relative_url);
+        let html_file_path = page_file_path(url);
+
+        // Avec --convert-links, la copie locale a été réécrite : il faut la page
+        // d'origine pour retrouver les liens, donc pas de requête conditionnelle
+        let local_copy = (config.timestamping && !convert_links).then(|| Path::new(&html_file_path));
+        let page = match fetch_with_retry(self.client, url, config, local_copy, &self.start, false).await {
+            Ok(page) => page,
+            Err(e) => {
+                if let Some(http_error) = e.downcast_ref::<HttpStatusError>().filter(|_| depth == 0) {
+                    eprintln!(
+                        "Error: Failed to fetch the website. Status: {}",
+                        http_error.status
+                    );
+                }
+                return Err(e);
+            }
+        };
+
+        let html = match &page {
+            Some(page) => String::from_utf8_lossy(&page.content).into_owned(),
+            None => {
+                println!("Server file no newer than local file {} -- not retrieving.", html_file_path);
+                fs::read_to_string(&html_file_path).await?
+            }
+        };
+        let document = Html::parse_document(&html);
+
+        // Au-delà de --level, les liens de la page ne sont plus suivis ; ses ressources
+        // (images, feuilles de style) restent récupérées selon --page-requisites-depth.
+        // `<meta name="robots">` peut aussi interdire de suivre les liens ou d'enregistrer la page.
+        let meta = match &self.robots {
+            Some(_) => MetaRobots::parse(&document),
+            None => MetaRobots::default(),
+        };
+        let follow_links = config.mirror.follows_links(depth) && !meta.nofollow;
+        let fetch_requisites = config.mirror.fetches_requisites(depth);
+
+        create_dir_all(&dir_name)?;
+        let mut modified_html = html.clone();
+
+        let link_selector = Selector::parse("a[href], img[src], link[href]").unwrap();
+        let reject_types_set = if let Some(types) = self.reject_types {
+            types.split(',').collect::<HashSet<&str>>()
+        } else {
+            HashSet::new()
+        };
 
-                if let Some(link) = attr {
-                    if let Some(extension) = link.split('.').last() {
-                        if reject_types_set.contains(extension) {
-                            println!("Skipping file: {} due to reject rules.", link);
-                            continue;
-                        }
+        let exclude_dirs_set = if let Some(dirs) = self.exclude_dirs {
+            dirs.split(',').collect::<HashSet<&str>>()
+        } else {
+            HashSet::new()
+        };
+
+        let base_url = Url::parse(url)?;
+        // Sous-pages et ressources, téléchargées par la file d'attente pendant
+        // l'analyse des autres pages
+        let mut discovered = Vec::new();
+
+        // Remplacement des liens dans les balises a, img, link
+        for element in document.select(&link_selector) {
+            let attr = match element.value().name() {
+                "img" => element.value().attr("src"),
+                "a" => element.value().attr("href"),
+                "link" => element.value().attr("href"),
+                _ => None,
+            };
+
+            if let Some(link) = attr {
+                if let Some(extension) = link.split('.').next_back() {
+                    if reject_types_set.contains(extension) {
+                        println!("Skipping file: {} due to reject rules.", link);
+                        continue;
                     }
+                }
 
-                    if let Some(folder) = extract_parent_directory(link) {
-                        println!("{link}");
-                        let folder_path = format!("/{}", folder);
-                        if exclude_dirs_set.contains(folder_path.as_str()) {
-                            
-                            continue;
-                        }
+                if let Some(folder) = extract_parent_directory(link) {
+                    println!("{link}");
+                    let folder_path = format!("/{}", folder);
+                    if exclude_dirs_set.contains(folder_path.as_str()) {
+                    
+                        continue;
                     }
+                }
 
-                    let file_url = match Url::parse(link) {
-                        Ok(url) => url.to_string(),
-                        Err(_) => base_url.join(link)?.to_string(),
-                    };
-
-                    let file_name = link.split(&domain).last().unwrap();
-                    let save_path = format!("{}/{}", dir_name, file_name);
-
-                    if !file_url.contains("#") {
-
-                    if file_url.contains(&domain) && (link.ends_with('/') || !file_name.contains('.')) {
-                        subpages_to_visit.push_back(file_url.clone());
-                        // Si c'est un répertoire, ajouter "/index.html" pour les liens convertis
-                        if convert_links {
-                            let relative_path = format!("./{}/index.html", link.trim_start_matches('/'));
-                            let cleaned_path = clean_path_segments(&relative_path);
-                            modified_html = replace_exact_link(&modified_html, link, &cleaned_path);
-                        }
-                    } else if file_url.contains(&domain) {
-                        download_resource(&client, &file_url, &save_path).await?;
-
-                        if convert_links {
-                            // Eviter de doubler les chemins relatifs (comme "./index.html")
-                            let relative_path = format!("./{}", file_name);
-                            let cleaned_path = clean_path_segments(&relative_path);
-                            modified_html = replace_exact_link(&modified_html, link, &cleaned_path);
-                        }
+                let Some(target) = resolve_link(&base_url, link) else {
+                    continue;
+                };
+                let file_url = target.to_string();
+                // Les liens vers l'hôte de la page sont rangés dans son répertoire, ceux
+                // vers un autre hôte (-H, --span-requisites) dans `./hôte/chemin`
+                let internal = same_host(&base_url, &target);
+                let requisite = element.value().name() != "a";
+                let file_name = if internal {
+                    link.split(domain).last().unwrap()
+                } else {
+                    target.path()
+                };
+                let save_path = format!("{}/{}", dir_name, file_name);
+
+                if !file_url.contains("#") {
+
+                // Un lien `<a>` mène à la profondeur suivante, une image ou une feuille de style
+                // est une ressource de la page ; hors limite, le lien converti reste absolu
+                let wanted = match element.value().name() {
+                    // rel="nofollow" exclut ce seul lien
+                    "a" => follow_links && !(self.robots.is_some() && has_nofollow(&element)),
+                    _ => fetch_requisites,
+                };
+                if !wanted || !config.mirror.accepts(&self.start, &target, requisite) {
+                    if convert_links && internal {
+                        modified_html = replace_exact_link(&modified_html, link, &file_url);
                     }
+                    continue;
                 }
-                
+
+                let is_page = link.ends_with('/') || !file_name.contains('.');
+                if !internal {
+                    // Une ressource d'un autre hôte n'est jamais analysée comme une page
+                    let is_page = is_page && !requisite;
+                    let local_path = foreign_path(&target, is_page);
+                    let local_link = format!("{}{}", "../".repeat(path_depth(relative_url)), local_path);
+                    if is_page {
+                        discovered.push(Job::Page {
+                            url: page_key(target.clone()),
+                            depth: depth + 1,
+                        });
+                    } else {
+                        discovered.push(Job::Resource {
+                            url: file_url.clone(),
+                            save_path: format!("./{}", local_path),
+                        });
+                    }
+                    if convert_links {
+                        modified_html = replace_link(&modified_html, link, &local_link);
+                    }
+                } else if is_page {
+                    discovered.push(Job::Page {
+                        url: page_key(target.clone()),
+                        depth: depth + 1,
+                    });
+                    // Si c'est un répertoire, ajouter "/index.html" pour les liens convertis
+                    if convert_links {
+                        let relative_path = format!("./{}/index.html", link.trim_start_matches('/'));
+                        let cleaned_path = clean_path_segments(&relative_path);
+                        modified_html = replace_exact_link(&modified_html, link, &cleaned_path);
+                    }
+                } else {
+                    discovered.push(Job::Resource {
+                        url: file_url.clone(),
+                        save_path,
+                    });
+
+                    if convert_links {
+                        // Eviter de doubler les chemins relatifs (comme "./index.html")
+                        let relative_path = format!("./{}", file_name);
+                        let cleaned_path = clean_path_segments(&relative_path);
+                        modified_html = replace_exact_link(&modified_html, link, &cleaned_path);
+                    }
                 }
             }
-
-            // Gérer les balises <style> et ressources CSS
-            let style_selector = Selector::parse("style").unwrap();
-            for element in document.select(&style_selector) {
-                let style_content = element.inner_html();
-                let updated_style = handle_css_resources(&client, &base_url, &dir_name, &style_content).await?;
-                modified_html = modified_html.replace( &style_content, &updated_style);
+        
             }
+        }
 
-            // Sauvegarder le HTML modifié avec les liens convertis
-            if convert_links {
-                file.write_all(modified_html.as_bytes()).await?;
-            } else {
-                file.write_all(html.as_bytes()).await?;
-            }
+        // Gérer les balises <style> et ressources CSS
+        let style_selector = Selector::parse("style").unwrap();
+        for element in document.select(&style_selector).filter(|_| fetch_requisites) {
+            let style_content = element.inner_html();
+            let updated_style = handle_css_resources(&base_url, &dir_name, &style_content, &mut discovered, |url| {
+                config.mirror.accepts(&self.start, url, true)
+            });
+            modified_html = modified_html.replace( &style_content, &updated_style);
+        }
 
-            while let Some(subpage_url) = subpages_to_visit.pop_front() {
-                Box::pin(mirror_recursive(&subpage_url, reject_types, exclude_dirs, convert_links, Arc::clone(&visited_urls))).await?;
+        // Sauvegarder le HTML modifié avec les liens convertis
+        if meta.noindex {
+            println!("Not saving {} (robots noindex)", html_file_path);
+        } else if let Some(page) = &page {
+            let content = if convert_links { &modified_html } else { &html };
+            write_atomically(Path::new(&html_file_path), content.as_bytes()).await?;
+            if config.timestamping {
+                timestamp::record(Path::new(&html_file_path), &page.headers)?;
             }
+        }
 
-            println!("Mirroring completed: {}", dir_name);
-            Ok(())
-        } else {
-            eprintln!(
-                "Error: Failed to fetch the website. Status: {}",
-                response.status()
-            );
-            Err(Box::from("Failed to download the website"))
+        println!("Mirroring completed: {}", dir_name);
+        let saved = (!meta.noindex).then_some(html_file_path);
+        Ok((saved, discovered))
+    }
+}
+
+// Directives de `<meta name="robots" content="...">`
+#[derive(Debug, Default)]
+struct MetaRobots {
+    nofollow: bool,
+    noindex: bool,
+}
+
+impl MetaRobots {
+    fn parse(document: &Html) -> Self {
+        let selector = Selector::parse("meta[name][content]").unwrap();
+        let mut meta = MetaRobots::default();
+        for element in document.select(&selector) {
+            if !element.value().attr("name").unwrap_or_default().eq_ignore_ascii_case("robots") {
+                continue;
+            }
+            for directive in element.value().attr("content").unwrap_or_default().split(',') {
+                match directive.trim().to_ascii_lowercase().as_str() {
+                    "nofollow" => meta.nofollow = true,
+                    "noindex" => meta.noindex = true,
+                    "none" => {
+                        meta.nofollow = true;
+                        meta.noindex = true;
+                    }
+                    _ => {}
+                }
+            }
         }
+        meta
     }
+}
 
-    mirror_recursive(url, reject_types, exclude_dirs, convert_links, visited_urls).await
+fn has_nofollow(element: &scraper::ElementRef) -> bool {
+    element
+        .value()
+        .attr("rel")
+        .is_some_and(|rel| rel.split_whitespace().any(|value| value.eq_ignore_ascii_case("nofollow")))
 }
 
-// Téléchargement des ressources individuelles
+// Téléchargement des ressources individuelles ; renvoie le fichier enregistré
 async fn download_resource(
     client: &Client,
     file_url: &str,
     save_path: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let response = client.get(file_url).send().await?;
-
-    if response.status().is_success() {
-        let save_path = Path::new(save_path);
-
-        if let Some(parent) = save_path.parent() {
-            fs::create_dir_all(parent).await?;
-        }
+    config: &DownloadConfig,
+    start: &Url,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let saved = Some(save_path.to_string());
+    let save_path = Path::new(save_path);
+    let local_copy = config.timestamping.then_some(save_path);
+
+    let Some(resource) = fetch_with_retry(client, file_url, config, local_copy, start, true).await? else {
+        println!("Not modified: {}", save_path.display());
+        return Ok(saved);
+    };
+
+    if let Some(parent) = save_path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
 
-        let mut file = File::create(save_path).await?;
-        let content = response.bytes().await?;
-        file.write_all(&content).await?;
-        println!("Downloaded: {}", save_path.display());
-    } else {
-        eprintln!("Error downloading: {}", file_url);
+    write_atomically(save_path, &resource.content).await?;
+    if config.timestamping {
+        timestamp::record(save_path, &resource.headers)?;
     }
+    println!("Downloaded: {}", save_path.display());
 
-    Ok(())
+    Ok(saved)
 }
 
-// Gérer et convertir les ressources CSS
-async fn handle_css_resources(
+// Contenu d'une page ou d'une ressource, avec les en-têtes de la réponse
+struct Fetched {
+    headers: HeaderMap,
+    content: Vec<u8>,
+}
+
+// Récupère le contenu d'une ressource en réessayant selon la politique --tries.
+// Avec une copie locale (mode -N), la requête est conditionnelle et `None`
+// signifie que la copie est à jour. Les redirections sont soumises aux mêmes
+// règles d'hôte que les liens (`requisite` pour une image ou une feuille de style).
+async fn fetch_with_retry(
     client: &Client,
+    file_url: &str,
+    config: &DownloadConfig,
+    local_copy: Option<&Path>,
+    start: &Url,
+    requisite: bool,
+) -> Result<Option<Fetched>, Box<dyn std::error::Error>> {
+    let attempt = async || -> Result<Option<Fetched>, Box<dyn std::error::Error>> {
+        let mut request = config.request.build(client, file_url);
+        if let Some(path) = local_copy {
+            request = request.headers(timestamp::conditional_headers(path));
+        }
+        // Comme pour les liens, une redirection ne sort pas des hôtes acceptés
+        let mut on_redirect = |location: &Url| {
+            if !config.mirror.accepts(start, location, requisite) {
+                return Err(RedirectError::Rejected(location.clone()));
+            }
+            println!("Location: {} [following]", location);
+            Ok(())
+        };
+        let response = config.request.send_following(request, &mut on_redirect);
+        let response = idle(config.timeouts.read, response).await??;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(HttpStatusError::from_response(file_url, &response).into());
+        }
+        let headers = response.headers().clone();
+        let mut content = Vec::new();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = idle(config.timeouts.read, stream.next()).await? {
+            content.extend_from_slice(&chunk?);
+        }
+        Ok(Some(Fetched { headers, content }))
+    };
+    config
+        .retry
+        .run(attempt, |e, delay, _| eprintln!("{}. Retrying in {:.1}s...", e, delay.as_secs_f32()))
+        .await
+}
+
+// Gérer et convertir les ressources CSS (ajoutées à `discovered`)
+fn handle_css_resources(
     base_url: &Url,
     dir_name: &str,
     css_content: &str,
-) -> Result<String, Box<dyn std::error::Error>> {
+    discovered: &mut Vec<Job>,
+    accepts: impl Fn(&Url) -> bool,
+) -> String {
     let re = Regex::new(r#"url\(\s*['"]?([^'"]+)['"]?\s*\)"#).unwrap();
     let mut updated_css = css_content.to_string();
 
     for cap in re.captures_iter(css_content) {
         if let Some(link) = cap.get(1) {
-            let file_url = match Url::parse(link.as_str()) {
-                Ok(url) => url.to_string(),
-                Err(_) => base_url.join(link.as_str())?.to_string(),
+            let Some(target) = resolve_link(base_url, link.as_str()) else {
+                continue;
             };
+            // Ressource d'un hôte hors du miroir : le lien reste inchangé
+            if !accepts(&target) {
+                continue;
+            }
 
-            let file_name = link.as_str().split('/').last().unwrap();
+            let file_name = link.as_str().split('/').next_back().unwrap();
             let save_path = format!("{}/{}", dir_name, file_name);
             // let save_path1 = format!("./{}", file_name);
 
             // Télécharger les ressources CSS
-            download_resource(client, &file_url, &save_path).await?;
+            discovered.push(Job::Resource {
+                url: target.to_string(),
+                save_path,
+            });
 
             // Remplacer le lien dans le CSS par le chemin local
-            updated_css = updated_css.replace(link.as_str(), &file_name);
+            updated_css = updated_css.replace(link.as_str(), file_name);
         }
     }
 
-    Ok(updated_css)
+    updated_css
 }
 
 
 
 // Remplacer les liens de manière plus précise pour éviter la corruption de balises
 fn replace_exact_link(content: &str, original: &str, replacement: &str) -> String {
-    let cleaned_replacement = clean_path_segments(replacement);
+    replace_link(content, original, &clean_path_segments(replacement))
+}
+
+// Comme `replace_exact_link`, sans nettoyer le chemin (qui écraserait les "../" successifs)
+fn replace_link(content: &str, original: &str, replacement: &str) -> String {
     let re = Regex::new(&format!(r#"(?P<before>[="\(']){}(?P<after>[)"'])"#, regex::escape(original))).unwrap();
     let result = re.replace_all(content, |caps: &regex::Captures| {
-        format!("{}{}{}", &caps["before"], cleaned_replacement, &caps["after"])
+        format!("{}{}{}", &caps["before"], replacement, &caps["after"])
     });
     result.to_string()
 }
 
+// Chemin local d'une URL d'un autre hôte, relatif au répertoire du miroir :
+// `hôte[:port]/chemin`, avec `index.html` pour une page
+fn foreign_path(url: &Url, is_page: bool) -> String {
+    if is_page {
+        // Même répertoire que celui que `Crawl::page` crée pour cette URL
+        let relative_url = url.as_str().trim_start_matches("http://").trim_start_matches("https://");
+        return format!("{}/index.html", relative_url.trim_end_matches('/'));
+    }
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let path = url.path();
+    if path.ends_with('/') {
+        format!("{}{}index.html", host, path)
+    } else {
+        format!("{}{}", host, path)
+    }
+}
+
+// Nombre de répertoires entre le répertoire du miroir et celui d'une page
+fn path_depth(relative_url: &str) -> usize {
+    relative_url.split('/').filter(|segment| !segment.is_empty()).count()
+}
+
 // Nettoie les segments d'un chemin en supprimant les répétitions
 fn clean_path_segments(path: &str) -> String {
     let segments: Vec<&str> = path.split('/').collect();
@@ -263,3 +800,66 @@ fn extract_parent_directory(path: &str) -> Option<&str> {
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(value: &str) -> Url {
+        Url::parse(value).unwrap()
+    }
+
+    fn options(span_hosts: bool, span_requisites: bool, domains: &[&str], exclude: &[&str]) -> MirrorOptions {
+        MirrorOptions {
+            span_hosts,
+            span_requisites,
+            domains: domains.iter().map(|domain| domain.to_string()).collect(),
+            exclude_domains: exclude.iter().map(|domain| domain.to_string()).collect(),
+            ..MirrorOptions::default()
+        }
+    }
+
+    #[test]
+    fn stays_on_start_host_by_default() {
+        let start = url("https://example.com/");
+        let mirror = MirrorOptions::default();
+        assert!(mirror.accepts(&start, &url("https://example.com/a/b.html"), false));
+        // Le nom du site dans la requête ou dans un sous-domaine ne suffit pas
+        assert!(!mirror.accepts(&start, &url("https://evil.com/?x=example.com"), false));
+        assert!(!mirror.accepts(&start, &url("https://example.com.evil.com/"), true));
+        assert!(!mirror.accepts(&start, &url("https://cdn.example.com/logo.png"), true));
+        // Autre port, autre serveur
+        assert!(!mirror.accepts(&start, &url("https://example.com:8443/"), false));
+        assert!(!mirror.accepts(&start, &url("ftp://example.com/file"), true));
+    }
+
+    #[test]
+    fn span_requisites_only_for_resources() {
+        let start = url("https://example.com/");
+        let mirror = options(false, true, &[], &[]);
+        assert!(mirror.accepts(&start, &url("https://cdn.example.com/logo.png"), true));
+        assert!(!mirror.accepts(&start, &url("https://cdn.example.com/page.html"), false));
+    }
+
+    #[test]
+    fn domains_restrict_spanned_hosts() {
+        let start = url("https://example.com/");
+        let mirror = options(true, false, &["example.com"], &[]);
+        assert!(mirror.accepts(&start, &url("https://cdn.example.com/"), false));
+        assert!(!mirror.accepts(&start, &url("https://evil.com/?x=example.com"), false));
+        assert!(!mirror.accepts(&start, &url("https://notexample.com/"), false));
+
+        // Sans -H, -D n'ouvre aucun hôte
+        let mirror = options(false, false, &["example.com"], &[]);
+        assert!(!mirror.accepts(&start, &url("https://cdn.example.com/"), false));
+    }
+
+    #[test]
+    fn excluded_domains_win_even_for_start_host() {
+        let start = url("https://www.example.com/");
+        let mirror = options(true, false, &[], &["ads.example.com", "www.example.com"]);
+        assert!(!mirror.accepts(&start, &url("https://x.ads.example.com/banner.png"), true));
+        assert!(!mirror.accepts(&start, &url("https://www.example.com/page"), false));
+        assert!(mirror.accepts(&start, &url("https://other.org/"), false));
+    }
+}
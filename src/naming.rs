@@ -0,0 +1,345 @@
+use percent_encoding::percent_decode_str;
+use reqwest::header::CONTENT_DISPOSITION;
+use reqwest::Response;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use url::Url;
+
+use crate::config::DownloadConfig;
+
+// Nom utilisé quand ni l'URL ni le serveur ne fournissent de nom exploitable
+const DEFAULT_FILE_NAME: &str = "index.html";
+
+// Avec --content-disposition ou --trust-server-names, le nom du fichier local
+// n'est connu qu'une fois la réponse reçue
+pub fn names_from_server(config: &DownloadConfig) -> bool {
+    config.content_disposition || config.trust_server_names
+}
+
+// Nom du fichier local d'après la réponse à la requête elle-même : l'en-tête
+// Content-Disposition, puis l'URL finale après redirection, sinon l'URL demandée
+pub fn file_name_from_response(response: &Response, url: &str, config: &DownloadConfig) -> String {
+    if config.content_disposition {
+        let from_header = response
+            .headers()
+            .get(CONTENT_DISPOSITION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(content_disposition_file_name);
+        if let Some(name) = from_header {
+            return name;
+        }
+    }
+    if config.trust_server_names {
+        return file_name_from_url(response.url().as_str());
+    }
+    file_name_from_url(url)
+}
+
+// Dernier segment du chemin de l'URL, décodé, sans la query ni le fragment
+pub fn file_name_from_url(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| {
+            let segment = url.path_segments()?.next_back()?.to_string();
+            sanitize(&percent_decode_str(&segment).decode_utf8_lossy())
+        })
+        .unwrap_or_else(|| DEFAULT_FILE_NAME.to_string())
+}
+
+// Nom de fichier d'un en-tête Content-Disposition (RFC 6266) ; `filename*`
+// (encodé selon la RFC 5987) est prioritaire sur `filename`
+pub fn content_disposition_file_name(value: &str) -> Option<String> {
+    let mut plain = None;
+    let mut extended = None;
+
+    for (key, param) in parse_parameters(value) {
+        match key.as_str() {
+            "filename" => plain = Some(param),
+            "filename*" => extended = decode_ext_value(&param),
+            _ => {}
+        }
+    }
+
+    extended
+        .and_then(|name| sanitize(&name))
+        .or_else(|| plain.and_then(|name| sanitize(&name)))
+}
+
+// Découpe `disposition; clé=valeur; clé="valeur"` en paires (clé en minuscules),
+// en respectant les guillemets et les échappements
+fn parse_parameters(value: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut chars = value.chars().peekable();
+
+    // Le type de disposition (attachment, inline) précède le premier ';'
+    for c in chars.by_ref() {
+        if c == ';' {
+            break;
+        }
+    }
+
+    loop {
+        let key: String = chars.by_ref().take_while(|&c| c != '=').collect();
+        let key = key.trim().to_ascii_lowercase();
+        if key.is_empty() {
+            break;
+        }
+
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let mut param = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => param.extend(chars.next()),
+                    '"' => break,
+                    _ => param.push(c),
+                }
+            }
+            // Ignorer ce qui reste jusqu'au paramètre suivant
+            for c in chars.by_ref() {
+                if c == ';' {
+                    break;
+                }
+            }
+        } else {
+            param = chars.by_ref().take_while(|&c| c != ';').collect();
+            param = param.trim().to_string();
+        }
+        params.push((key, param));
+    }
+
+    params
+}
+
+// Valeur étendue RFC 5987 : `charset'langue'octets%XX`
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?.to_ascii_lowercase();
+    let _language = parts.next()?;
+    let encoded = parts.next()?;
+    let bytes: Vec<u8> = percent_decode_str(encoded).collect();
+
+    match charset.as_str() {
+        "utf-8" => String::from_utf8(bytes).ok(),
+        // ISO-8859-1 : chaque octet correspond au point de code Unicode de même valeur
+        "iso-8859-1" => Some(bytes.iter().map(|&b| b as char).collect()),
+        _ => None,
+    }
+}
+
+// Ne garde que le nom de base : un serveur ne doit pas pouvoir écrire ailleurs
+// que dans le dossier de destination
+fn sanitize(name: &str) -> Option<String> {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    let cleaned: String = base.chars().filter(|c| !c.is_control()).collect();
+    let cleaned = cleaned.trim();
+    match cleaned {
+        "" | "." | ".." => None,
+        _ => Some(cleaned.to_string()),
+    }
+}
+
+// Que faire quand le fichier de destination existe déjà
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ClobberPolicy {
+    // Comme wget : file.zip.1, file.zip.2...
+    #[default]
+    Number,
+    // --no-clobber : ne pas retélécharger
+    NoClobber,
+    // --backups N : file.zip devient file.zip.1, file.zip.1 devient file.zip.2...
+    Backups(u32),
+    // -O, -c et -N : écrire sur le fichier existant
+    Overwrite,
+}
+
+// Chemins réservés par les téléchargements en cours de ce processus, avec le
+// nombre de sauvegardes à faire tourner au moment de remplacer le fichier (--backups)
+static RESERVED: LazyLock<Mutex<HashMap<PathBuf, Option<u32>>>> = LazyLock::new(Default::default);
+
+// Chemin de destination réservé, libéré à la fin du téléchargement
+pub struct Reservation {
+    path: PathBuf,
+}
+
+impl Reservation {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        RESERVED.lock().unwrap().remove(&self.path);
+    }
+}
+
+// Choisit le chemin final selon la politique. Le choix et la réservation se font
+// sous le même verrou, pour que deux téléchargements parallèles vers le même nom
+// n'écrivent jamais dans le même fichier. `None` : fichier existant et --no-clobber.
+pub fn reserve_path(path: &Path, policy: ClobberPolicy) -> io::Result<Option<Reservation>> {
+    let mut reserved = RESERVED.lock().unwrap();
+
+    let mut backups = None;
+    let chosen = if reserved.contains_key(path) {
+        // Un autre téléchargement du lot utilise déjà ce nom
+        if policy == ClobberPolicy::NoClobber {
+            return Ok(None);
+        }
+        next_free_path(path, &reserved)
+    } else if path.exists() {
+        match policy {
+            ClobberPolicy::NoClobber => return Ok(None),
+            ClobberPolicy::Number => next_free_path(path, &reserved),
+            // La rotation attend que le nouveau fichier soit complet (`rotate_backups`) :
+            // -N lit encore la copie locale, et un échec ne doit rien déplacer
+            ClobberPolicy::Backups(count) => {
+                backups = Some(count);
+                path.to_path_buf()
+            }
+            ClobberPolicy::Overwrite => path.to_path_buf(),
+        }
+    } else {
+        path.to_path_buf()
+    };
+
+    reserved.insert(chosen.clone(), backups);
+    Ok(Some(Reservation { path: chosen }))
+}
+
+// Premier `nom.N` libre, sur le disque comme parmi les réservations
+fn next_free_path(path: &Path, reserved: &HashMap<PathBuf, Option<u32>>) -> PathBuf {
+    (1..)
+        .map(|n| numbered(path, n))
+        .find(|candidate| !candidate.exists() && !reserved.contains_key(candidate))
+        .unwrap()
+}
+
+// Appelée juste avant de remplacer `path` par le fichier téléchargé : décale les
+// sauvegardes existantes si le chemin a été réservé avec --backups. Au-delà de
+// `count`, la plus ancienne est écrasée ; avec -c, le fichier repris est déjà
+// devenu le `.part` et il n'y a rien à sauvegarder.
+pub fn rotate_backups(path: &Path) -> io::Result<()> {
+    let count = RESERVED.lock().unwrap().get(path).copied().flatten().unwrap_or(0);
+    if count == 0 || !path.exists() {
+        return Ok(());
+    }
+    for n in (1..count).rev() {
+        let from = numbered(path, n);
+        if from.exists() {
+            fs::rename(&from, numbered(path, n + 1))?;
+        }
+    }
+    fs::rename(path, numbered(path, 1))
+}
+
+fn numbered(path: &Path, n: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Exemples de la RFC 6266, section 5
+    #[test]
+    fn rfc6266_plain_filename() {
+        assert_eq!(
+            content_disposition_file_name("Attachment; filename=example.html").as_deref(),
+            Some("example.html")
+        );
+        assert_eq!(
+            content_disposition_file_name("INLINE; FILENAME= \"an example.html\"").as_deref(),
+            Some("an example.html")
+        );
+    }
+
+    #[test]
+    fn rfc6266_extended_filename() {
+        assert_eq!(
+            content_disposition_file_name("attachment; filename*= UTF-8''%e2%82%ac%20rates").as_deref(),
+            Some("€ rates")
+        );
+    }
+
+    #[test]
+    fn extended_filename_wins_over_plain() {
+        let value = "attachment; filename=\"EURO rates\"; filename*=utf-8''%e2%82%ac%20rates";
+        assert_eq!(content_disposition_file_name(value).as_deref(), Some("€ rates"));
+        // L'ordre des paramètres ne change rien
+        let value = "attachment; filename*=utf-8''%e2%82%ac%20rates; filename=\"EURO rates\"";
+        assert_eq!(content_disposition_file_name(value).as_deref(), Some("€ rates"));
+    }
+
+    // RFC 5987, section 3.2.2
+    #[test]
+    fn rfc5987_iso_8859_1_with_language() {
+        assert_eq!(
+            content_disposition_file_name("attachment; filename*=iso-8859-1'en'%A3%20rates").as_deref(),
+            Some("£ rates")
+        );
+    }
+
+    #[test]
+    fn unknown_charset_falls_back_to_plain() {
+        let value = "attachment; filename=\"fallback.txt\"; filename*=koi8-r''%C1";
+        assert_eq!(content_disposition_file_name(value).as_deref(), Some("fallback.txt"));
+    }
+
+    #[test]
+    fn quoted_string_escapes() {
+        assert_eq!(
+            content_disposition_file_name(r#"attachment; filename="a \"quoted\" name.txt"; size=3"#).as_deref(),
+            Some("a \"quoted\" name.txt")
+        );
+    }
+
+    #[test]
+    fn directories_are_stripped() {
+        assert_eq!(
+            content_disposition_file_name("attachment; filename=\"../../etc/passwd\"").as_deref(),
+            Some("passwd")
+        );
+        assert_eq!(
+            content_disposition_file_name("attachment; filename=\"..\\\\evil.exe\"").as_deref(),
+            Some("evil.exe")
+        );
+        assert_eq!(content_disposition_file_name("attachment; filename=\"..\""), None);
+        assert_eq!(content_disposition_file_name("attachment"), None);
+    }
+
+    #[test]
+    fn backups_rotate_only_when_committing() {
+        let dir = std::env::temp_dir().join(format!("wget-backups-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file.txt");
+        fs::write(&path, "v2").unwrap();
+        fs::write(numbered(&path, 1), "v1").unwrap();
+
+        let reservation = reserve_path(&path, ClobberPolicy::Backups(2)).unwrap().unwrap();
+        assert_eq!(reservation.path(), path);
+        // Rien n'a bougé tant que le téléchargement n'est pas terminé
+        assert_eq!(fs::read_to_string(&path).unwrap(), "v2");
+        assert!(!numbered(&path, 2).exists());
+
+        rotate_backups(&path).unwrap();
+        assert!(!path.exists());
+        assert_eq!(fs::read_to_string(numbered(&path, 1)).unwrap(), "v2");
+        assert_eq!(fs::read_to_string(numbered(&path, 2)).unwrap(), "v1");
+
+        // Sans réservation --backups, aucune rotation
+        drop(reservation);
+        fs::write(&path, "v3").unwrap();
+        rotate_backups(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "v3");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+use clap::ArgMatches;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::{sleep_until, Instant};
+
+// Capacité du seau à jetons : une seule requête d'avance, pour qu'aucune
+// seconde ne dépasse --rate-per-host requêtes
+const BURST: f64 = 1.0;
+
+// Espacement des requêtes vers un même serveur (--wait, --random-wait,
+// --rate-per-host, Crawl-delay). Chaque requête réserve son créneau sous le
+// verrou puis attend : les téléchargements simultanés vers un hôte se
+// succèdent sans jamais dépasser le rythme demandé.
+#[derive(Debug, Default)]
+pub struct Pacing {
+    wait: Option<Duration>,
+    random_wait: bool,
+    // Requêtes par seconde autorisées vers chaque hôte
+    rate: Option<f64>,
+    hosts: Mutex<HashMap<String, HostSlot>>,
+}
+
+#[derive(Debug)]
+struct HostSlot {
+    // Premier instant où la prochaine requête peut partir (--wait, Crawl-delay)
+    next: Instant,
+    // Seau à jetons : jetons disponibles à l'instant `refilled` (négatif quand
+    // des requêtes ont déjà réservé les jetons à venir)
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Pacing {
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, String> {
+        let wait = match matches.get_one::<f64>("wait") {
+            Some(&secs) if !secs.is_finite() || secs < 0.0 => return Err(format!("Invalid wait time: {}", secs)),
+            Some(&secs) if secs > 0.0 => Some(Duration::from_secs_f64(secs)),
+            _ => None,
+        };
+        let rate = match matches.get_one::<f64>("rate_per_host") {
+            Some(&rate) if !rate.is_finite() || rate <= 0.0 => {
+                return Err(format!("Invalid request rate: {}", rate))
+            }
+            rate => rate.copied(),
+        };
+        Ok(Pacing {
+            wait,
+            random_wait: matches.get_flag("random_wait"),
+            rate,
+            hosts: Mutex::default(),
+        })
+    }
+
+    // Attend le tour de la prochaine requête vers `origin` ; `crawl_delay` vient
+    // du robots.txt de l'hôte et s'impose s'il dépasse --wait
+    pub async fn wait_turn(&self, origin: &str, crawl_delay: Option<Duration>) {
+        let interval = self.interval().max(crawl_delay.unwrap_or_default());
+        if interval.is_zero() && self.rate.is_none() {
+            return;
+        }
+
+        let slot = {
+            let mut hosts = self.hosts.lock().unwrap();
+            let now = Instant::now();
+            let host = hosts.entry(origin.to_string()).or_insert(HostSlot {
+                next: now,
+                tokens: BURST,
+                refilled: now,
+            });
+
+            let mut slot = host.next.max(now);
+            if let Some(rate) = self.rate {
+                host.tokens = (host.tokens + now.duration_since(host.refilled).as_secs_f64() * rate).min(BURST);
+                host.refilled = now;
+                host.tokens -= 1.0;
+                if host.tokens < 0.0 {
+                    slot = slot.max(now + Duration::from_secs_f64(-host.tokens / rate));
+                }
+            }
+            host.next = slot + interval;
+            slot
+        };
+        sleep_until(slot).await;
+    }
+
+    // --wait, tiré entre 0,5 et 1,5 fois sa valeur avec --random-wait
+    fn interval(&self) -> Duration {
+        let Some(wait) = self.wait else {
+            return Duration::ZERO;
+        };
+        if !self.random_wait {
+            return wait;
+        }
+        wait.mul_f64(0.5 + jitter())
+    }
+}
+
+// Nombre pseudo-aléatoire dans [0, 1) : chaque `RandomState` reçoit des clés
+// aléatoires, ce qui suffit pour brouiller le rythme des requêtes
+fn jitter() -> f64 {
+    let value = RandomState::new().hash_one(Instant::now());
+    (value >> 11) as f64 / (1u64 << 53) as f64
+}
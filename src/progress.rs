@@ -0,0 +1,183 @@
+use indicatif::{HumanBytes, HumanDuration, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::io::IsTerminal;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Intervalle entre deux lignes d'état quand la sortie n'est pas un terminal
+const PLAIN_INTERVAL: Duration = Duration::from_secs(5);
+
+// Affichage d'un lot -i : une barre par transfert actif et une barre globale
+// (fichiers terminés, octets reçus, débit). Hors terminal, des lignes de texte
+// périodiques remplacent les barres.
+pub struct BatchProgress {
+    // `None` quand la sortie standard n'est pas un terminal
+    multi: Option<MultiProgress>,
+    overall: ProgressBar,
+    state: Mutex<BatchState>,
+    started: Instant,
+}
+
+#[derive(Default)]
+struct BatchState {
+    active: Vec<ProgressBar>,
+    total: usize,
+    done: usize,
+    // Octets des transferts terminés
+    finished_bytes: u64,
+    last_plain: Option<Instant>,
+}
+
+impl BatchProgress {
+    pub fn new() -> Self {
+        let multi = std::io::stdout()
+            .is_terminal()
+            .then(|| MultiProgress::with_draw_target(ProgressDrawTarget::stdout()));
+        let overall = match &multi {
+            Some(multi) => {
+                let bar = multi.add(ProgressBar::new_spinner());
+                bar.set_style(
+                    ProgressStyle::with_template("[{elapsed_precise}] {msg} {bytes} ({bytes_per_sec})")
+                        .expect("valid progress template"),
+                );
+                bar
+            }
+            None => ProgressBar::hidden(),
+        };
+        BatchProgress {
+            multi,
+            overall,
+            // Première ligne d'état après un intervalle complet
+            state: Mutex::new(BatchState {
+                last_plain: Some(Instant::now()),
+                ..BatchState::default()
+            }),
+            started: Instant::now(),
+        }
+    }
+
+    // Une entrée de plus dans le lot (les URL lues sur stdin arrivent au fil de l'eau)
+    pub fn add_entry(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.total += 1;
+        self.update_overall(&state);
+    }
+
+    // Barre d'un transfert qui démarre ; `fetch_to_file` en fixe la taille et l'avance
+    pub fn start(&self, name: &str) -> ProgressBar {
+        let bar = match &self.multi {
+            Some(multi) => {
+                let bar = multi.insert_before(&self.overall, ProgressBar::new(0));
+                bar.set_style(
+                    ProgressStyle::with_template(
+                        "{msg:24!} [{bar:30}] {bytes}/{total_bytes} {bytes_per_sec} ({eta})",
+                    )
+                    .expect("valid progress template")
+                    .progress_chars("=>-"),
+                );
+                bar
+            }
+            None => ProgressBar::hidden(),
+        };
+        bar.set_message(name.to_string());
+        self.state.lock().unwrap().active.push(bar.clone());
+        bar
+    }
+
+    // Retire la barre d'un transfert terminé (ou abandonné) et renvoie son bilan
+    // pour la ligne de résumé qui la remplace
+    pub fn finish(&self, bar: &ProgressBar) -> String {
+        let mut state = self.state.lock().unwrap();
+        bar.finish_and_clear();
+        state.active.retain(|active| !active.is_finished());
+        state.finished_bytes += bar.position();
+        if let Some(multi) = &self.multi {
+            multi.remove(bar);
+        }
+        self.update_overall(&state);
+        format!("{} in {}", HumanBytes(bar.position()), HumanDuration(bar.elapsed()))
+    }
+
+    // Une entrée du lot est traitée, avec ou sans succès
+    pub fn entry_done(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.done += 1;
+        self.update_overall(&state);
+    }
+
+    // Message au-dessus des barres, sans les effacer
+    pub fn println(&self, message: &str) {
+        match &self.multi {
+            Some(multi) => {
+                let _ = multi.println(message);
+            }
+            None => println!("{}", message),
+        }
+    }
+
+    // Les messages d'erreur vont sur stderr : les barres sont retirées le temps de l'écriture
+    pub fn eprintln(&self, message: &str) {
+        match &self.multi {
+            Some(multi) => multi.suspend(|| eprintln!("{}", message)),
+            None => eprintln!("{}", message),
+        }
+    }
+
+    // Appelée régulièrement : met à jour le total d'octets, ou affiche l'état hors terminal
+    pub fn tick(&self) {
+        let mut state = self.state.lock().unwrap();
+        self.update_overall(&state);
+        if self.multi.is_some() || state.active.is_empty() {
+            return;
+        }
+        if state.last_plain.is_some_and(|last| last.elapsed() < PLAIN_INTERVAL) {
+            return;
+        }
+        state.last_plain = Some(Instant::now());
+
+        println!("{}", self.status_line(&state));
+        for bar in &state.active {
+            let percent = match bar.length() {
+                Some(length) if length > 0 => format!("{}%", bar.position() * 100 / length),
+                _ => "?%".to_string(),
+            };
+            println!(
+                "  {}: {} of {} ({})",
+                bar.message(),
+                HumanBytes(bar.position()),
+                HumanBytes(bar.length().unwrap_or(0)),
+                percent
+            );
+        }
+    }
+
+    // Bilan final du lot
+    pub fn finish_all(&self) {
+        let state = self.state.lock().unwrap();
+        // Plus aucune barre à l'écran : affichage direct
+        self.overall.finish_and_clear();
+        println!("Batch complete: {}", self.status_line(&state));
+    }
+
+    fn received(&self, state: &BatchState) -> u64 {
+        state.finished_bytes + state.active.iter().map(ProgressBar::position).sum::<u64>()
+    }
+
+    fn update_overall(&self, state: &BatchState) {
+        self.overall.set_message(format!("{}/{} files", state.done, state.total));
+        self.overall.set_position(self.received(state));
+    }
+
+    fn status_line(&self, state: &BatchState) -> String {
+        let elapsed = self.started.elapsed();
+        let received = self.received(state);
+        let speed = received as f64 / elapsed.as_secs_f64().max(0.001);
+        format!(
+            "{}/{} files, {} in {} ({}/s)",
+            state.done,
+            state.total,
+            HumanBytes(received),
+            HumanDuration(elapsed),
+            HumanBytes(speed as u64)
+        )
+    }
+}
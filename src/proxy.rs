@@ -0,0 +1,117 @@
+use clap::ArgMatches;
+use url::Url;
+
+use crate::hosts::in_domain;
+
+// Proxys à utiliser selon le schéma de l'URL demandée, avec les hôtes à joindre
+// directement (no_proxy)
+#[derive(Clone, Debug, Default)]
+pub struct ProxySettings {
+    http: Option<Url>,
+    https: Option<Url>,
+    no_proxy: Vec<String>,
+}
+
+impl ProxySettings {
+    // --proxy l'emporte sur les variables d'environnement ; --no-proxy désactive tout proxy
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, String> {
+        if matches.get_flag("no_proxy") {
+            return Ok(ProxySettings::default());
+        }
+
+        let explicit = matches.get_one::<String>("proxy").map(|proxy| parse_proxy(proxy)).transpose()?;
+        let all = env_proxy(&["all_proxy", "ALL_PROXY"])?;
+        let mut settings = ProxySettings {
+            http: match &explicit {
+                Some(proxy) => Some(proxy.clone()),
+                None => env_proxy(&["http_proxy", "HTTP_PROXY"])?.or_else(|| all.clone()),
+            },
+            https: match &explicit {
+                Some(proxy) => Some(proxy.clone()),
+                None => env_proxy(&["https_proxy", "HTTPS_PROXY"])?.or(all),
+            },
+            no_proxy: env_var(&["no_proxy", "NO_PROXY"])
+                .map(|list| {
+                    list.split(',')
+                        .map(|entry| entry.trim().to_ascii_lowercase())
+                        .filter(|entry| !entry.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        };
+
+        // --proxy-user / --proxy-password remplacent les identifiants inclus dans l'URL du proxy
+        if let Some(user) = matches.get_one::<String>("proxy_user") {
+            let password = matches.get_one::<String>("proxy_password").map(String::as_str);
+            for proxy in [&mut settings.http, &mut settings.https].into_iter().flatten() {
+                proxy
+                    .set_username(user)
+                    .and_then(|_| proxy.set_password(Some(password.unwrap_or_default())))
+                    .map_err(|_| format!("Cannot set credentials on proxy {}", proxy))?;
+            }
+        }
+
+        Ok(settings)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.http.is_none() && self.https.is_none()
+    }
+
+    // Proxy pour `url`, ou `None` pour une connexion directe
+    pub fn for_url(&self, url: &Url) -> Option<Url> {
+        let host = url.host_str()?.to_ascii_lowercase();
+        if self.no_proxy.iter().any(|entry| bypasses(entry, &host)) {
+            return None;
+        }
+        match url.scheme() {
+            "https" => self.https.clone(),
+            "http" => self.http.clone(),
+            _ => None,
+        }
+    }
+}
+
+// Entrée de no_proxy : `*`, un nom d'hôte ou un suffixe de domaine (`example.com`
+// et `.example.com` couvrent tous deux `www.example.com`), éventuellement suivi d'un port
+fn bypasses(entry: &str, host: &str) -> bool {
+    if entry == "*" {
+        return true;
+    }
+    let entry = entry.trim_start_matches("*.").trim_start_matches('.');
+    let entry = match entry.strip_prefix('[') {
+        // IPv6 entre crochets : le port éventuel suit le `]`
+        Some(rest) => rest.split(']').next().unwrap_or(rest),
+        // Un seul `:` sépare le port ; plusieurs désignent une IPv6 nue
+        None if entry.matches(':').count() == 1 => entry.split(':').next().unwrap_or(entry),
+        None => entry,
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    in_domain(host, entry)
+}
+
+// `proxy:3128` sans schéma désigne un proxy HTTP ; socks5:// et socks5h:// (résolution
+// DNS par le proxy) sont aussi acceptés
+fn parse_proxy(value: &str) -> Result<Url, String> {
+    let value = if value.contains("://") {
+        value.to_string()
+    } else {
+        format!("http://{}", value)
+    };
+    let url = Url::parse(&value).map_err(|e| format!("Invalid proxy URL {}: {}", value, e))?;
+    match url.scheme() {
+        "http" | "https" | "socks5" | "socks5h" => Ok(url),
+        scheme => Err(format!("Unsupported proxy scheme: {}", scheme)),
+    }
+}
+
+fn env_var(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|name| std::env::var(name).ok())
+        .filter(|value| !value.trim().is_empty())
+}
+
+fn env_proxy(names: &[&str]) -> Result<Option<Url>, String> {
+    env_var(names).map(|value| parse_proxy(value.trim())).transpose()
+}
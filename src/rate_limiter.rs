@@ -0,0 +1,54 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+// Limiteur de débit partagé : plusieurs flux (segments) se répartissent le même budget
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    state: Mutex<State>,
+}
+
+struct State {
+    start: Instant,
+    consumed: u64,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        RateLimiter {
+            bytes_per_sec: bytes_per_sec.max(1),
+            state: Mutex::new(State {
+                start: Instant::now(),
+                consumed: 0,
+            }),
+        }
+    }
+
+    // Comptabilise `bytes` octets reçus et attend le temps nécessaire pour rester sous la limite
+    pub async fn consume(&self, bytes: usize) {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            state.consumed += bytes as u64;
+            let expected = Duration::from_secs_f64(state.consumed as f64 / self.bytes_per_sec as f64);
+            expected.saturating_sub(state.start.elapsed())
+        };
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+    }
+}
+
+// Analyse une limite de débit du type "200k" ou "2M"
+pub fn parse_rate_limit(rate_limit: &str) -> Result<u64, String> {
+    let len = rate_limit.len();
+    if len == 0 {
+        return Err("Invalid rate limit format".to_string());
+    }
+    let (num_part, unit) = rate_limit.split_at(len - 1);
+    let num: u64 = num_part.parse().map_err(|_| "Invalid number in rate limit".to_string())?;
+    match unit {
+        "k" | "K" => Ok(num * 1024),
+        "M" | "m" => Ok(num * 1024 * 1024),
+        _ => Err("Invalid unit in rate limit, use 'k' or 'M'".to_string()),
+    }
+}
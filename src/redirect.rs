@@ -0,0 +1,132 @@
+use clap::ArgMatches;
+use reqwest::header::{
+    HeaderMap, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, LOCATION, PROXY_AUTHORIZATION,
+};
+use reqwest::{Method, Request, Response, StatusCode};
+use std::error::Error;
+use std::fmt;
+use url::Url;
+
+// Suivi des redirections (--max-redirect, --no-redirect), fait à la main pour
+// pouvoir afficher et contrôler chaque étape
+#[derive(Clone, Debug)]
+pub struct RedirectPolicy {
+    // Nombre maximal de redirections suivies, 0 pour n'en suivre aucune
+    pub max: usize,
+    // Accepter qu'un site HTTPS redirige vers une URL HTTP
+    pub allow_downgrade: bool,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        // Même limite que GNU wget
+        RedirectPolicy {
+            max: 20,
+            allow_downgrade: false,
+        }
+    }
+}
+
+impl RedirectPolicy {
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        let mut policy = RedirectPolicy::default();
+        if matches.get_flag("no_redirect") {
+            policy.max = 0;
+        } else if let Some(&max) = matches.get_one::<usize>("max_redirect") {
+            policy.max = max;
+        }
+        policy.allow_downgrade = matches.get_flag("allow_https_downgrade");
+        policy
+    }
+
+    // Cible de la redirection annoncée par `response`, ou `None` s'il ne faut pas la
+    // suivre (pas une redirection, ou --no-redirect : la réponse 3xx est rendue telle quelle)
+    pub fn target(&self, response: &Response, hops: usize) -> Result<Option<Url>, RedirectError> {
+        if !is_redirect(response.status()) || self.max == 0 {
+            return Ok(None);
+        }
+        let Some(location) = response.headers().get(LOCATION).and_then(|value| value.to_str().ok()) else {
+            return Ok(None);
+        };
+        let target = response
+            .url()
+            .join(location)
+            .map_err(|_| RedirectError::InvalidLocation(location.to_string()))?;
+
+        if hops >= self.max {
+            return Err(RedirectError::TooMany(self.max));
+        }
+        if response.url().scheme() == "https" && target.scheme() == "http" && !self.allow_downgrade {
+            return Err(RedirectError::Downgrade(target));
+        }
+        Ok(Some(target))
+    }
+}
+
+fn is_redirect(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::SEE_OTHER
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT
+    )
+}
+
+// Prépare la requête suivante à partir de la précédente, comme les navigateurs :
+// 303 (et 301/302 après un POST) repasse en GET sans corps, 307/308 rejouent la requête
+// à l'identique. Les en-têtes d'authentification et cookies explicites ne suivent pas
+// un changement d'hôte.
+pub fn follow(mut request: Request, status: StatusCode, target: Url) -> Request {
+    let to_get = status == StatusCode::SEE_OTHER && *request.method() != Method::HEAD
+        || matches!(status, StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND)
+            && *request.method() == Method::POST;
+    if to_get {
+        *request.method_mut() = Method::GET;
+        *request.body_mut() = None;
+        request.headers_mut().remove(CONTENT_TYPE);
+        request.headers_mut().remove(CONTENT_LENGTH);
+    }
+    if !same_host(request.url(), &target) {
+        remove_sensitive_headers(request.headers_mut());
+    }
+    *request.url_mut() = target;
+    request
+}
+
+// Même hôte et même port
+pub fn same_host(a: &Url, b: &Url) -> bool {
+    a.host_str() == b.host_str() && a.port_or_known_default() == b.port_or_known_default()
+}
+
+fn remove_sensitive_headers(headers: &mut HeaderMap) {
+    headers.remove(AUTHORIZATION);
+    headers.remove(PROXY_AUTHORIZATION);
+    headers.remove(COOKIE);
+}
+
+// Redirection refusée
+#[derive(Debug)]
+pub enum RedirectError {
+    TooMany(usize),
+    Downgrade(Url),
+    InvalidLocation(String),
+    // Refusée par l'appelant (par exemple vers un autre hôte en mode miroir)
+    Rejected(Url),
+}
+
+impl fmt::Display for RedirectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RedirectError::TooMany(max) => write!(f, "{} redirections exceeded", max),
+            RedirectError::Downgrade(url) => {
+                write!(f, "Refusing to follow redirect from HTTPS to {}", url)
+            }
+            RedirectError::InvalidLocation(location) => write!(f, "Invalid redirect location: {}", location),
+            RedirectError::Rejected(url) => write!(f, "Not following redirect to {}", url),
+        }
+    }
+}
+
+impl Error for RedirectError {}
@@ -0,0 +1,216 @@
+use indicatif::HumanBytes;
+use reqwest::StatusCode;
+use serde::Serialize;
+use std::error::Error;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::checksum::ChecksumMismatch;
+use crate::redirect::RedirectError;
+use crate::retry::HttpStatusError;
+use crate::tls::PinnedKeyMismatch;
+
+// Codes de sortie de GNU wget, plus un code pour les empreintes invalides
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitCode {
+    Success = 0,
+    Generic = 1,
+    Parse = 2,
+    Io = 3,
+    Network = 4,
+    Ssl = 5,
+    Auth = 6,
+    Protocol = 7,
+    Server = 8,
+    Checksum = 9,
+}
+
+impl ExitCode {
+    // Comme wget, le code le plus bas l'emporte quand plusieurs erreurs se
+    // produisent, sauf 0 et 1 qui cèdent la place à tout code plus précis
+    pub fn combine(self, other: ExitCode) -> ExitCode {
+        match (self, other) {
+            (ExitCode::Success, code) | (code, ExitCode::Success) => code,
+            (ExitCode::Generic, code) | (code, ExitCode::Generic) => code,
+            (a, b) => {
+                if (a as i32) <= (b as i32) {
+                    a
+                } else {
+                    b
+                }
+            }
+        }
+    }
+
+    // Code correspondant à une erreur de téléchargement, en remontant ses causes
+    pub fn for_error(error: &(dyn Error + 'static)) -> ExitCode {
+        let mut current = Some(error);
+        while let Some(error) = current {
+            if let Some(code) = classify(error) {
+                return code;
+            }
+            current = error.source();
+        }
+        ExitCode::Generic
+    }
+}
+
+fn classify(error: &(dyn Error + 'static)) -> Option<ExitCode> {
+    if error.is::<ChecksumMismatch>() {
+        return Some(ExitCode::Checksum);
+    }
+    if error.is::<PinnedKeyMismatch>() {
+        return Some(ExitCode::Ssl);
+    }
+    if error.is::<RedirectError>() {
+        return Some(ExitCode::Protocol);
+    }
+    if let Some(e) = error.downcast_ref::<HttpStatusError>() {
+        return Some(match e.status {
+            StatusCode::UNAUTHORIZED | StatusCode::PROXY_AUTHENTICATION_REQUIRED => ExitCode::Auth,
+            _ => ExitCode::Server,
+        });
+    }
+    if let Some(e) = error.downcast_ref::<reqwest::Error>() {
+        // Les erreurs du backend TLS n'ont pas de type propre : on les reconnaît à leur message
+        if e.is_connect() && mentions_tls(e) {
+            return Some(ExitCode::Ssl);
+        }
+        if e.is_decode() || e.is_redirect() || e.is_builder() {
+            return Some(ExitCode::Protocol);
+        }
+        return Some(ExitCode::Network);
+    }
+    if let Some(e) = error.downcast_ref::<io::Error>() {
+        return Some(match e.kind() {
+            io::ErrorKind::TimedOut
+            | io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted => ExitCode::Network,
+            _ => ExitCode::Io,
+        });
+    }
+    None
+}
+
+fn mentions_tls(error: &(dyn Error + 'static)) -> bool {
+    let mut current = Some(error);
+    while let Some(error) = current {
+        let message = error.to_string().to_ascii_lowercase();
+        if ["certificate", "ssl", "tls", "handshake"].iter().any(|word| message.contains(word)) {
+            return true;
+        }
+        current = error.source();
+    }
+    false
+}
+
+// Résultat d'une URL, pour le tableau de fin d'exécution et --report-json
+#[derive(Clone, Debug, Serialize)]
+pub struct Entry {
+    pub url: String,
+    pub status: &'static str,
+    pub exit_code: i32,
+    #[serde(skip)]
+    code: ExitCode,
+    pub error: Option<String>,
+    pub bytes: Option<u64>,
+    // Durée en secondes
+    pub duration: f64,
+    pub path: Option<String>,
+}
+
+impl Entry {
+    // `path` est le fichier enregistré (`None` pour la sortie standard)
+    pub fn new(url: &str, result: &Result<Option<String>, Box<dyn Error>>, duration: Duration) -> Self {
+        let (status, code, error, path) = match result {
+            Ok(path) => ("ok", ExitCode::Success, None, path.clone()),
+            Err(e) => ("failed", ExitCode::for_error(e.as_ref()), Some(e.to_string()), None),
+        };
+        Entry {
+            url: url.to_string(),
+            status,
+            exit_code: code as i32,
+            code,
+            error,
+            bytes: path
+                .as_ref()
+                .and_then(|path| std::fs::metadata(path).ok())
+                .map(|metadata| metadata.len()),
+            duration: duration.as_secs_f64(),
+            path,
+        }
+    }
+}
+
+// Bilan de l'exécution, partagé par tous les téléchargements
+#[derive(Debug, Default)]
+pub struct Report {
+    entries: Mutex<Vec<Entry>>,
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    exit_code: i32,
+    succeeded: usize,
+    failed: usize,
+    entries: &'a [Entry],
+}
+
+impl Report {
+    pub fn record(&self, entry: Entry) {
+        self.entries.lock().unwrap().push(entry);
+    }
+
+    pub fn exit_code(&self) -> ExitCode {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|entry| entry.code)
+            .fold(ExitCode::Success, ExitCode::combine)
+    }
+
+    // Tableau URL / statut / taille / durée / fichier, utile dès qu'il y a plusieurs URL
+    pub fn print_table(&self) {
+        let entries = self.entries.lock().unwrap();
+        if entries.len() < 2 {
+            return;
+        }
+        let url_width = entries.iter().map(|entry| entry.url.len()).max().unwrap_or(0).clamp(3, 60);
+        println!("{:<url_width$}  {:<6}  {:>10}  {:>8}  PATH", "URL", "STATUS", "BYTES", "TIME");
+        for entry in entries.iter() {
+            let url = if entry.url.len() > url_width {
+                format!("{}...", &entry.url[..entry.url.floor_char_boundary(url_width - 3)])
+            } else {
+                entry.url.clone()
+            };
+            println!(
+                "{:<url_width$}  {:<6}  {:>10}  {:>7.1}s  {}",
+                url,
+                entry.status,
+                entry.bytes.map(|bytes| HumanBytes(bytes).to_string()).unwrap_or_else(|| "-".to_string()),
+                entry.duration,
+                entry.path.as_deref().or(entry.error.as_deref()).unwrap_or("-")
+            );
+        }
+        let failed = entries.iter().filter(|entry| entry.error.is_some()).count();
+        println!("{} succeeded, {} failed", entries.len() - failed, failed);
+    }
+
+    // --report-json : bilan lisible par une CI
+    pub fn write_json(&self, path: &Path, exit_code: ExitCode) -> io::Result<()> {
+        let entries = self.entries.lock().unwrap();
+        let failed = entries.iter().filter(|entry| entry.error.is_some()).count();
+        let report = JsonReport {
+            exit_code: exit_code as i32,
+            succeeded: entries.len() - failed,
+            failed,
+            entries: &entries,
+        };
+        let json = serde_json::to_string_pretty(&report).map_err(io::Error::other)?;
+        std::fs::write(path, json + "\n")
+    }
+}
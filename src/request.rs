@@ -0,0 +1,200 @@
+use clap::ArgMatches;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, REFERER, USER_AGENT};
+use reqwest::{Client, Method, RequestBuilder, Response};
+use std::error::Error;
+use url::Url;
+
+use crate::auth::Auth;
+use crate::redirect::{follow, same_host, RedirectError, RedirectPolicy};
+use crate::tls::PinnedKeys;
+
+// User-Agent envoyé par défaut, certains serveurs refusant les clients inconnus
+const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/58.0.3029.110 Safari/537.3";
+
+// En-têtes, méthode et corps appliqués à toutes les requêtes (fichier unique, -i, --mirror)
+#[derive(Clone, Debug)]
+pub struct RequestOptions {
+    pub headers: HeaderMap,
+    pub method: Method,
+    pub body: Option<Vec<u8>>,
+    // Identifiants (--user, --bearer-token, `.netrc`)
+    pub auth: Auth,
+    // Seul hôte à recevoir --user et --bearer-token (l'hôte de départ du miroir) ;
+    // `None` : l'hôte de chaque requête, avant redirection
+    pub credentials_origin: Option<Url>,
+    // Clés publiques acceptées pour les serveurs HTTPS (--pinnedpubkey)
+    pub pinned_keys: Option<PinnedKeys>,
+    pub redirect: RedirectPolicy,
+}
+
+impl Default for RequestOptions {
+    fn default() -> Self {
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, HeaderValue::from_static(DEFAULT_USER_AGENT));
+        RequestOptions {
+            headers,
+            method: Method::GET,
+            body: None,
+            auth: Auth::default(),
+            credentials_origin: None,
+            pinned_keys: None,
+            redirect: RedirectPolicy::default(),
+        }
+    }
+}
+
+impl RequestOptions {
+    // --user-agent, --referer et --header (qui a le dernier mot), puis --method et le corps
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, String> {
+        let mut options = RequestOptions {
+            auth: Auth::from_matches(matches)?,
+            pinned_keys: matches
+                .get_one::<String>("pinned_pubkey")
+                .map(|value| PinnedKeys::parse(value))
+                .transpose()?,
+            redirect: RedirectPolicy::from_matches(matches),
+            ..RequestOptions::default()
+        };
+
+        if let Some(agent) = matches.get_one::<String>("user_agent") {
+            options.set_header(USER_AGENT, agent)?;
+        }
+        if let Some(referer) = matches.get_one::<String>("referer") {
+            options.set_header(REFERER, referer)?;
+        }
+
+        // --post-data et --post-file envoient un formulaire, --body-file (avec --method) un corps brut
+        let form = if let Some(data) = matches.get_one::<String>("post_data") {
+            options.body = Some(data.clone().into_bytes());
+            true
+        } else if let Some(path) = matches.get_one::<String>("post_file") {
+            options.body = Some(read_body(path)?);
+            true
+        } else {
+            if let Some(path) = matches.get_one::<String>("body_file") {
+                options.body = Some(read_body(path)?);
+            }
+            false
+        };
+        if form {
+            options.method = Method::POST;
+            options.headers.insert(
+                CONTENT_TYPE,
+                HeaderValue::from_static("application/x-www-form-urlencoded"),
+            );
+        }
+
+        if let Some(headers) = matches.get_many::<String>("header") {
+            options.add_headers(headers.map(String::as_str))?;
+        }
+
+        if let Some(method) = matches.get_one::<String>("method") {
+            options.method = Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                .map_err(|_| format!("Invalid HTTP method: {}", method))?;
+        }
+
+        Ok(options)
+    }
+
+    // Requête vers `url` avec la méthode, les en-têtes et le corps configurés
+    pub fn build(&self, client: &Client, url: &str) -> RequestBuilder {
+        let request = client
+            .request(self.method.clone(), url)
+            .headers(self.headers.clone());
+        match &self.body {
+            Some(body) => request.body(body.clone()),
+            None => request,
+        }
+    }
+
+    // Envoie une requête construite par `build` en suivant les redirections
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, Box<dyn Error>> {
+        self.send_following(request, &mut |_| Ok(())).await
+    }
+
+    // Comme `send`, en soumettant chaque redirection à `on_redirect` (pour l'afficher,
+    // ou la refuser). Chaque étape passe par l'authentification ; avec --pinnedpubkey,
+    // la clé publique d'une origine est vérifiée avant de lui envoyer quoi que ce
+    // soit, puis sur chacune de ses réponses.
+    pub async fn send_following(
+        &self,
+        request: RequestBuilder,
+        on_redirect: &mut dyn FnMut(&Url) -> Result<(), RedirectError>,
+    ) -> Result<Response, Box<dyn Error>> {
+        let (client, request) = request.build_split();
+        let mut request = request?;
+        let origin = self
+            .credentials_origin
+            .clone()
+            .unwrap_or_else(|| request.url().clone());
+        let mut hops = 0;
+
+        loop {
+            let next = request.try_clone();
+            let explicit_allowed = same_host(&origin, request.url());
+            if let Some(pinned_keys) = &self.pinned_keys {
+                pinned_keys.probe(&client, request.url()).await?;
+            }
+            let response = self
+                .auth
+                .send(&client, request, explicit_allowed, self.pinned_keys.as_ref())
+                .await?;
+
+            let (Some(target), Some(next)) = (self.redirect.target(&response, hops)?, next) else {
+                return Ok(response);
+            };
+            on_redirect(&target)?;
+            request = follow(next, response.status(), target);
+            hops += 1;
+        }
+    }
+
+    // Les sondages (HEAD, plage du premier octet) et le découpage en segments
+    // n'ont de sens que pour une simple lecture
+    pub fn is_plain_get(&self) -> bool {
+        self.method == Method::GET && self.body.is_none()
+    }
+
+    // Lignes `Nom: valeur` : elles remplacent les en-têtes par défaut de même nom,
+    // et un nom répété est envoyé plusieurs fois. Comme wget, `Nom:` sans valeur
+    // retire l'en-tête.
+    fn add_headers<'a>(&mut self, lines: impl Iterator<Item = &'a str>) -> Result<(), String> {
+        let mut custom = HeaderMap::new();
+        let mut removed = Vec::new();
+        for line in lines {
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| format!("Invalid header '{}', expected 'Name: value'", line))?;
+            let name = HeaderName::from_bytes(name.trim().as_bytes())
+                .map_err(|_| format!("Invalid header name in '{}'", line))?;
+            let value = value.trim();
+            if value.is_empty() {
+                custom.remove(&name);
+                removed.push(name);
+                continue;
+            }
+            let value = HeaderValue::from_str(value)
+                .map_err(|_| format!("Invalid value for header {}: {}", name, value))?;
+            custom.append(name, value);
+        }
+
+        for name in removed.iter().chain(custom.keys()) {
+            self.headers.remove(name);
+        }
+        for (name, value) in custom.iter() {
+            self.headers.append(name, value.clone());
+        }
+        Ok(())
+    }
+
+    fn set_header(&mut self, name: HeaderName, value: &str) -> Result<(), String> {
+        let value = HeaderValue::from_str(value)
+            .map_err(|_| format!("Invalid value for header {}: {}", name, value))?;
+        self.headers.insert(name, value);
+        Ok(())
+    }
+}
+
+fn read_body(path: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("Cannot read {}: {}", path, e))
+}
@@ -0,0 +1,144 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Response, StatusCode};
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+use tokio::time::sleep;
+
+// Attente maximale acceptée d'un Retry-After ; --waitretry ne plafonne que le backoff
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+// Politique de nouvelles tentatives (--tries, --waitretry, --retry-on-http-error)
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    // Nombre total de tentatives, 0 pour réessayer indéfiniment
+    pub tries: u32,
+    // Délai maximal entre deux tentatives (le backoff exponentiel est plafonné)
+    pub wait_retry: Duration,
+    // Statuts HTTP considérés comme des erreurs temporaires
+    pub retry_on_http_error: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        // Mêmes valeurs par défaut que GNU wget
+        RetryPolicy {
+            tries: 20,
+            wait_retry: Duration::from_secs(10),
+            retry_on_http_error: Vec::new(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Délai à attendre avant la tentative suivante, ou `None` si l'erreur est
+    // définitive ou si toutes les tentatives ont été consommées
+    pub fn delay_for(&self, error: &(dyn Error + 'static), attempt: u32) -> Option<Duration> {
+        if self.tries != 0 && attempt >= self.tries {
+            return None;
+        }
+
+        if let Some(http_error) = error.downcast_ref::<HttpStatusError>() {
+            if !self.retry_on_http_error.contains(&http_error.status.as_u16()) {
+                return None;
+            }
+            // Le serveur indique lui-même quand revenir
+            if let Some(delay) = http_error.retry_after {
+                return Some(delay.min(MAX_RETRY_AFTER));
+            }
+        } else if !is_transient(error) {
+            return None;
+        }
+
+        Some(self.backoff(attempt))
+    }
+
+    // Appelle `attempt` jusqu'au succès, à une erreur définitive ou à l'épuisement
+    // des tentatives ; `on_retry` annonce chaque nouvel essai (erreur, délai,
+    // numéro de la tentative suivante) avant l'attente
+    pub async fn run<T>(
+        &self,
+        mut attempt: impl AsyncFnMut() -> Result<T, Box<dyn Error>>,
+        mut on_retry: impl FnMut(&(dyn Error + 'static), Duration, u32),
+    ) -> Result<T, Box<dyn Error>> {
+        let mut count = 0;
+        loop {
+            count += 1;
+            match attempt().await {
+                Ok(value) => return Ok(value),
+                Err(e) => match self.delay_for(e.as_ref(), count) {
+                    Some(delay) => {
+                        on_retry(e.as_ref(), delay, count + 1);
+                        sleep(delay).await;
+                    }
+                    None => return Err(e),
+                },
+            }
+        }
+    }
+
+    // Backoff exponentiel : 1s, 2s, 4s... plafonné par --waitretry
+    fn backoff(&self, attempt: u32) -> Duration {
+        let secs = 1u64 << attempt.saturating_sub(1).min(16);
+        Duration::from_secs(secs).min(self.wait_retry)
+    }
+
+    // Nombre de tentatives affiché dans les messages ("inf" si illimité)
+    pub fn tries_label(&self) -> String {
+        if self.tries == 0 {
+            "inf".to_string()
+        } else {
+            self.tries.to_string()
+        }
+    }
+}
+
+// Réponse HTTP en erreur (statut non 2xx)
+#[derive(Debug)]
+pub struct HttpStatusError {
+    pub url: String,
+    pub status: StatusCode,
+    pub retry_after: Option<Duration>,
+}
+
+impl HttpStatusError {
+    pub fn from_response(url: &str, response: &Response) -> Self {
+        HttpStatusError {
+            url: url.to_string(),
+            status: response.status(),
+            retry_after: retry_after(response.headers()),
+        }
+    }
+}
+
+impl fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to download: {}. Status: {}", self.url, self.status)
+    }
+}
+
+impl Error for HttpStatusError {}
+
+// Lit l'en-tête Retry-After, exprimé en secondes ou sous forme de date HTTP
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+// Les erreurs réseau (connexion, timeout, flux interrompu) valent la peine d'être retentées,
+// contrairement aux erreurs d'écriture sur le disque ou de réponse invalide
+fn is_transient(error: &(dyn Error + 'static)) -> bool {
+    if let Some(e) = error.downcast_ref::<reqwest::Error>() {
+        return e.is_timeout() || e.is_connect() || e.is_request() || e.is_body() || e.is_decode();
+    }
+    // Connexion fermée avant d'avoir reçu tous les octets annoncés, ou serveur muet
+    // au-delà de --read-timeout
+    matches!(
+        error.downcast_ref::<std::io::Error>().map(|e| e.kind()),
+        Some(std::io::ErrorKind::UnexpectedEof | std::io::ErrorKind::TimedOut)
+    )
+}
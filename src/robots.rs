@@ -0,0 +1,330 @@
+use reqwest::header::USER_AGENT;
+use reqwest::Client;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use url::Url;
+
+use crate::config::DownloadConfig;
+use crate::timeouts::idle;
+
+// Règles robots.txt retenues pour notre user-agent sur un hôte
+#[derive(Debug, Default)]
+pub struct Rules {
+    // (motif, autorisé) ; la règle la plus longue qui correspond l'emporte
+    rules: Vec<(String, bool)>,
+    crawl_delay: Option<Duration>,
+}
+
+impl Rules {
+    // robots.txt injoignable ou en erreur serveur : tout est interdit (RFC 9309)
+    fn disallow_all() -> Self {
+        Rules {
+            rules: vec![("/".to_string(), false)],
+            crawl_delay: None,
+        }
+    }
+
+    // Groupes `User-agent` : celui dont le nom figure dans notre user-agent (le
+    // plus précis en cas de choix), sinon le groupe `*`
+    pub fn parse(contents: &str, user_agent: &str) -> Self {
+        let user_agent = user_agent.to_ascii_lowercase();
+        // (agents du groupe, règles, délai)
+        let mut groups: Vec<(Vec<String>, Rules)> = Vec::new();
+        let mut in_agents = false;
+
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    // Plusieurs lignes User-agent consécutives partagent un groupe
+                    if !in_agents {
+                        groups.push((Vec::new(), Rules::default()));
+                        in_agents = true;
+                    }
+                    if let Some((agents, _)) = groups.last_mut() {
+                        agents.push(value.to_ascii_lowercase());
+                    }
+                }
+                "allow" | "disallow" => {
+                    in_agents = false;
+                    let allow = key.trim().eq_ignore_ascii_case("allow");
+                    // `Disallow:` vide n'interdit rien
+                    if let (Some((_, rules)), false) = (groups.last_mut(), value.is_empty()) {
+                        rules.rules.push((value.to_string(), allow));
+                    }
+                }
+                "crawl-delay" => {
+                    in_agents = false;
+                    if let (Some((_, rules)), Ok(secs)) = (groups.last_mut(), value.parse::<f64>()) {
+                        if secs.is_finite() && secs >= 0.0 {
+                            rules.crawl_delay = Some(Duration::from_secs_f64(secs));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let specific = groups
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (agents, _))| {
+                agents
+                    .iter()
+                    .filter(|agent| *agent != "*" && !agent.is_empty() && user_agent.contains(agent.as_str()))
+                    .map(|agent| agent.len())
+                    .max()
+                    .map(|len| (len, index))
+            })
+            .max()
+            .map(|(_, index)| index);
+        let chosen = specific.or_else(|| groups.iter().position(|(agents, _)| agents.iter().any(|agent| agent == "*")));
+        match chosen {
+            Some(index) => groups.swap_remove(index).1,
+            None => Rules::default(),
+        }
+    }
+
+    // Chemin et requête de l'URL confrontés aux règles ; à longueur égale, Allow l'emporte
+    pub fn allows(&self, url: &Url) -> bool {
+        let target = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        self.rules
+            .iter()
+            .filter(|(pattern, _)| pattern_matches(pattern, &target))
+            .max_by_key(|(pattern, allow)| (pattern.len(), *allow))
+            .is_none_or(|(_, allow)| *allow)
+    }
+}
+
+// `*` remplace n'importe quelle suite de caractères, `$` ancre la fin du chemin ;
+// sans `$`, le motif est un préfixe
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    for (index, part) in parts.iter().enumerate() {
+        // Le dernier morceau d'un motif ancré doit terminer le chemin
+        if anchored && index == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+// robots.txt de chaque hôte, téléchargé une seule fois
+#[derive(Debug, Default)]
+pub struct RobotsCache {
+    hosts: Mutex<HashMap<String, Arc<OnceCell<Rules>>>>,
+}
+
+impl RobotsCache {
+    // Vérifie que `url` peut être demandée ; renvoie le Crawl-delay de l'hôte, à
+    // respecter avant la requête
+    pub async fn permits(&self, client: &Client, url: &Url, config: &DownloadConfig) -> Result<Option<Duration>, ()> {
+        let origin = url.origin().ascii_serialization();
+        let cell = Arc::clone(self.hosts.lock().unwrap().entry(origin.clone()).or_default());
+        let rules = cell.get_or_init(|| fetch_rules(client, &origin, config)).await;
+        if rules.allows(url) {
+            Ok(rules.crawl_delay)
+        } else {
+            Err(())
+        }
+    }
+}
+
+// Un robots.txt absent (4xx) n'impose rien ; une erreur serveur ou réseau interdit tout
+async fn fetch_rules(client: &Client, origin: &str, config: &DownloadConfig) -> Rules {
+    let robots_url = format!("{}/robots.txt", origin);
+    let user_agent = config
+        .request
+        .headers
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    let request = client.get(&robots_url).headers(config.request.headers.clone());
+    let response = match idle(config.timeouts.read, config.request.send(request)).await {
+        Ok(Ok(response)) => response,
+        _ => {
+            eprintln!("Cannot retrieve {}: disallowing the whole host", robots_url);
+            return Rules::disallow_all();
+        }
+    };
+    let status = response.status();
+    if status.is_client_error() {
+        return Rules::default();
+    }
+    if !status.is_success() {
+        eprintln!("{} returned {}: disallowing the whole host", robots_url, status);
+        return Rules::disallow_all();
+    }
+    match idle(config.timeouts.read, response.text()).await {
+        Ok(Ok(contents)) => Rules::parse(&contents, &user_agent),
+        _ => Rules::disallow_all(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 9309, section 5.1
+    const RFC9309_EXAMPLE: &str = "\
+User-Agent: *
+Disallow: *.gif$
+Disallow: /example/
+Allow: /publications/
+
+User-Agent: foobot
+Disallow:/
+Allow:/example/page.html
+Allow:/example/allowed.gif
+
+User-Agent: barbot
+User-Agent: bazbot
+Disallow: /example/page.html
+
+User-Agent: quxbot
+";
+
+    fn allowed(rules: &Rules, path: &str) -> bool {
+        rules.allows(&Url::parse(&format!("http://example.com{}", path)).unwrap())
+    }
+
+    #[test]
+    fn rfc9309_groups() {
+        let rules = Rules::parse(RFC9309_EXAMPLE, "Mozilla/5.0 (compatible)");
+        assert!(!allowed(&rules, "/image.gif"));
+        assert!(allowed(&rules, "/image.gif?size=2"));
+        assert!(!allowed(&rules, "/example/"));
+        assert!(allowed(&rules, "/publications/"));
+        assert!(allowed(&rules, "/"));
+
+        let foobot = Rules::parse(RFC9309_EXAMPLE, "FooBot/1.2");
+        assert!(allowed(&foobot, "/example/page.html"));
+        assert!(allowed(&foobot, "/example/allowed.gif"));
+        assert!(!allowed(&foobot, "/example/other.html"));
+        assert!(!allowed(&foobot, "/"));
+
+        // Deux lignes User-Agent consécutives forment un seul groupe
+        for agent in ["barbot", "bazbot"] {
+            let rules = Rules::parse(RFC9309_EXAMPLE, agent);
+            assert!(!allowed(&rules, "/example/page.html"));
+            assert!(allowed(&rules, "/example/other.html"));
+        }
+
+        // Groupe vide : tout est permis, sans retomber sur `*`
+        let quxbot = Rules::parse(RFC9309_EXAMPLE, "quxbot");
+        assert!(allowed(&quxbot, "/example/"));
+        assert!(allowed(&quxbot, "/image.gif"));
+    }
+
+    // RFC 9309, section 5.2 : la règle la plus longue l'emporte
+    #[test]
+    fn longest_match_wins() {
+        let rules = Rules::parse(
+            "User-Agent: foobot\nAllow: /example/page/\nDisallow: /example/page/disallowed.gif\n",
+            "foobot",
+        );
+        assert!(allowed(&rules, "/example/page/"));
+        assert!(allowed(&rules, "/example/page/allowed.gif"));
+        assert!(!allowed(&rules, "/example/page/disallowed.gif"));
+    }
+
+    // RFC 9309, section 2.2.2 : à longueur égale, Allow l'emporte
+    #[test]
+    fn allow_wins_ties() {
+        let rules = Rules::parse("User-agent: *\nDisallow: /page\nAllow: /page\n", "any");
+        assert!(allowed(&rules, "/page"));
+        let rules = Rules::parse("User-agent: *\nAllow: /page\nDisallow: /page\n", "any");
+        assert!(allowed(&rules, "/page"));
+    }
+
+    #[test]
+    fn empty_disallow_and_missing_group() {
+        let rules = Rules::parse("User-agent: *\nDisallow:\n", "any");
+        assert!(allowed(&rules, "/anything"));
+        let rules = Rules::parse("User-agent: otherbot\nDisallow: /\n", "any");
+        assert!(allowed(&rules, "/anything"));
+    }
+
+    #[test]
+    fn most_specific_agent_group() {
+        let contents = "User-agent: bot\nDisallow: /a\n\nUser-agent: superbot\nDisallow: /b\n\nUser-agent: *\nDisallow: /\n";
+        let rules = Rules::parse(contents, "SuperBot/2.0");
+        assert!(allowed(&rules, "/a"));
+        assert!(!allowed(&rules, "/b"));
+    }
+
+    #[test]
+    fn comments_case_and_crawl_delay() {
+        let rules = Rules::parse(
+            "# robots\nUSER-AGENT: * # tout le monde\nDISALLOW: /private # secret\nCrawl-delay: 2.5\n",
+            "any",
+        );
+        assert!(!allowed(&rules, "/private/file"));
+        assert!(allowed(&rules, "/public"));
+        assert_eq!(rules.crawl_delay, Some(Duration::from_millis(2500)));
+        let rules = Rules::parse("User-agent: *\nCrawl-delay: soon\n", "any");
+        assert_eq!(rules.crawl_delay, None);
+    }
+
+    // RFC 9309, section 2.2.3 : `*` et `$`
+    #[test]
+    fn wildcards_and_anchors() {
+        assert!(pattern_matches("/fish", "/fish.html"));
+        assert!(pattern_matches("/fish", "/fishheads/yummy.html"));
+        assert!(!pattern_matches("/fish", "/Fish.asp"));
+
+        assert!(pattern_matches("/*.php", "/index.php"));
+        assert!(pattern_matches("/*.php", "/folder/filename.php?parameters"));
+        assert!(!pattern_matches("/*.php", "/windows.PHP"));
+
+        assert!(pattern_matches("/*.php$", "/filename.php"));
+        assert!(pattern_matches("/*.php$", "/folder/filename.php"));
+        assert!(!pattern_matches("/*.php$", "/filename.php?parameters"));
+        assert!(!pattern_matches("/*.php$", "/filename.php5"));
+
+        assert!(pattern_matches("/fish*.php", "/fishheads/catfish.php?parameters"));
+        assert!(!pattern_matches("/fish*.php", "/Fish.PHP"));
+
+        assert!(pattern_matches("/$", "/"));
+        assert!(!pattern_matches("/$", "/page"));
+        assert!(pattern_matches("*", "/anything"));
+    }
+
+    #[test]
+    fn query_is_part_of_the_match() {
+        let rules = Rules::parse("User-agent: *\nDisallow: /search?q=\n", "any");
+        assert!(!allowed(&rules, "/search?q=rust"));
+        assert!(allowed(&rules, "/search"));
+    }
+
+    #[test]
+    fn disallow_all_blocks_everything() {
+        let rules = Rules::disallow_all();
+        assert!(!allowed(&rules, "/"));
+        assert!(!allowed(&rules, "/robots.txt"));
+    }
+}
@@ -0,0 +1,239 @@
+use futures::future::try_join_all;
+use futures::stream::StreamExt;
+use indicatif::ProgressBar;
+use reqwest::header::{HeaderMap, CONTENT_RANGE, RANGE};
+use reqwest::{Client, StatusCode};
+use std::error::Error;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::fs::OpenOptions;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+use crate::download::parse_content_range;
+use crate::rate_limiter::RateLimiter;
+use crate::request::RequestOptions;
+use crate::retry::{HttpStatusError, RetryPolicy};
+use crate::timeouts::idle;
+use crate::utils::part_path;
+
+// En dessous de cette taille, découper le fichier ne fait que multiplier les connexions
+const MIN_SEGMENT_SIZE: u64 = 1024 * 1024;
+
+// Réponse du serveur à la requête de sondage
+pub struct RangeProbe {
+    pub total_size: u64,
+    pub headers: HeaderMap,
+}
+
+// Demande le premier octet du fichier pour savoir si le serveur accepte les requêtes
+// partielles. Renvoie la taille totale si c'est le cas.
+pub async fn probe_ranges(
+    client: &Client,
+    url: &str,
+    request: &RequestOptions,
+    read_timeout: Option<Duration>,
+) -> Option<RangeProbe> {
+    let probe = request.build(client, url).header(RANGE, "bytes=0-0");
+    let response = idle(read_timeout, request.send(probe)).await.ok()?.ok()?;
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return None;
+    }
+    let (_, total) = response
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_content_range)?;
+    Some(RangeProbe {
+        total_size: total.filter(|&size| size > 0)?,
+        headers: response.headers().clone(),
+    })
+}
+
+// Découpe `total_size` octets en au plus `segments` plages [début, fin] inclusives
+pub fn split_ranges(total_size: u64, segments: usize) -> Vec<(u64, u64)> {
+    let max_segments = total_size.div_ceil(MIN_SEGMENT_SIZE).max(1);
+    let count = (segments as u64).clamp(1, max_segments);
+    let segment_size = total_size.div_ceil(count);
+
+    (0..count)
+        .map(|i| i * segment_size)
+        .take_while(|&start| start < total_size)
+        .map(|start| (start, (start + segment_size).min(total_size) - 1))
+        .collect()
+}
+
+// Contexte partagé par tous les segments d'un même fichier
+pub struct SegmentedDownload<'a> {
+    pub client: &'a Client,
+    pub url: &'a str,
+    pub request: &'a RequestOptions,
+    pub save_path: &'a str,
+    pub pb: Option<&'a ProgressBar>,
+    pub limiter: Option<&'a RateLimiter>,
+    pub retry: &'a RetryPolicy,
+    pub read_timeout: Option<Duration>,
+}
+
+impl SegmentedDownload<'_> {
+    // Télécharge les plages en parallèle, chacune écrite à sa position dans un fichier
+    // préalloué. Celui-ci ne devient le `.part` qu'une fois toutes les plages reçues :
+    // rempli de zéros, il ne doit jamais servir de point de reprise à -c. C'est à
+    // l'appelant de renommer le `.part` une fois vérifié.
+    pub async fn run(&self, ranges: &[(u64, u64)]) -> Result<(), Box<dyn Error>> {
+        let total_size = ranges.last().map(|&(_, end)| end + 1).unwrap_or(0);
+        let work = self.work_path();
+
+        let file = tokio::fs::File::create(&work).await?;
+        file.set_len(total_size).await?;
+        drop(file);
+
+        if let Some(pb) = self.pb {
+            pb.set_length(total_size);
+            pb.set_position(0);
+            pb.reset_eta();
+        }
+
+        let segments = ranges.iter().map(|&(start, end)| self.fetch_segment(start, end));
+        if let Err(e) = try_join_all(segments).await {
+            let _ = tokio::fs::remove_file(&work).await;
+            return Err(e);
+        }
+        tokio::fs::rename(&work, part_path(Path::new(self.save_path))).await?;
+
+        Ok(())
+    }
+
+    // `nom.segments`, distinct du `.part` que lit la reprise
+    fn work_path(&self) -> PathBuf {
+        let mut name = Path::new(self.save_path).as_os_str().to_os_string();
+        name.push(".segments");
+        PathBuf::from(name)
+    }
+
+    // Télécharge une plage, en reprenant au dernier octet reçu en cas d'erreur temporaire
+    async fn fetch_segment(&self, start: u64, end: u64) -> Result<(), Box<dyn Error>> {
+        let mut position = start;
+        self.retry
+            .run(
+                async || self.fetch_range(&mut position, end).await,
+                |e, delay, _| {
+                    eprintln!(
+                        "Segment {}-{}: {}. Retrying in {:.1}s...",
+                        start,
+                        end,
+                        e,
+                        delay.as_secs_f32()
+                    )
+                },
+            )
+            .await
+    }
+
+    async fn fetch_range(&self, position: &mut u64, end: u64) -> Result<(), Box<dyn Error>> {
+        let range = self
+            .request
+            .build(self.client, self.url)
+            .header(RANGE, format!("bytes={}-{}", position, end));
+        let response = idle(self.read_timeout, self.request.send(range)).await??;
+
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            if !response.status().is_success() {
+                return Err(Box::new(HttpStatusError::from_response(self.url, &response)));
+            }
+            return Err(format!("Server ignored the range request for {}", self.url).into());
+        }
+        let resumed_at = response
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_content_range)
+            .map(|(start, _)| start);
+        if resumed_at != Some(*position) {
+            return Err(format!("Invalid Content-Range in partial response from {}", self.url).into());
+        }
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(self.work_path())
+            .await?;
+        file.seek(SeekFrom::Start(*position)).await?;
+        let mut stream = response.bytes_stream();
+
+        while let Some(chunk) = idle(self.read_timeout, stream.next()).await? {
+            let chunk = chunk?;
+            // Ne jamais déborder sur le segment suivant
+            let wanted = (end + 1 - *position).min(chunk.len() as u64) as usize;
+            file.write_all(&chunk[..wanted]).await?;
+            *position += wanted as u64;
+
+            if let Some(pb) = self.pb {
+                pb.inc(wanted as u64);
+            }
+            if let Some(limiter) = self.limiter {
+                limiter.consume(wanted).await;
+            }
+            if *position > end {
+                break;
+            }
+        }
+        file.flush().await?;
+
+        if *position <= end {
+            // Flux interrompu avant la fin de la plage : erreur temporaire, on reprendra ici
+            return Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!("connection closed at byte {} of segment ending at {}", position, end),
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Les plages se suivent sans trou ni chevauchement et couvrent tout le fichier
+    fn assert_covers(ranges: &[(u64, u64)], total_size: u64) {
+        assert_eq!(ranges.first().map(|&(start, _)| start), Some(0));
+        assert_eq!(ranges.last().map(|&(_, end)| end + 1), Some(total_size));
+        for pair in ranges.windows(2) {
+            assert_eq!(pair[0].1 + 1, pair[1].0);
+        }
+    }
+
+    #[test]
+    fn uneven_split() {
+        let total_size = 10 * MIN_SEGMENT_SIZE + 7;
+        let ranges = split_ranges(total_size, 3);
+        assert_eq!(ranges.len(), 3);
+        assert_covers(&ranges, total_size);
+        // Seul le dernier segment est plus court
+        let segment_size = ranges[0].1 + 1;
+        assert_eq!(ranges[1].1 - ranges[1].0 + 1, segment_size);
+        assert!(ranges[2].1 - ranges[2].0 < segment_size);
+    }
+
+    #[test]
+    fn small_file_single_segment() {
+        assert_eq!(split_ranges(MIN_SEGMENT_SIZE - 1, 8), vec![(0, MIN_SEGMENT_SIZE - 2)]);
+        assert_eq!(split_ranges(1, 4), vec![(0, 0)]);
+    }
+
+    #[test]
+    fn segments_limited_by_minimum_size() {
+        // 2,5 Mio : au plus trois segments, quel que soit --segments
+        let total_size = 5 * MIN_SEGMENT_SIZE / 2;
+        let ranges = split_ranges(total_size, 16);
+        assert_eq!(ranges.len(), 3);
+        assert_covers(&ranges, total_size);
+    }
+
+    #[test]
+    fn at_least_one_segment() {
+        let ranges = split_ranges(4 * MIN_SEGMENT_SIZE, 0);
+        assert_eq!(ranges, vec![(0, 4 * MIN_SEGMENT_SIZE - 1)]);
+    }
+}
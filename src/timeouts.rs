@@ -0,0 +1,104 @@
+use clap::ArgMatches;
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::ClientBuilder;
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Délais réseau (--connect-timeout, --dns-timeout, --read-timeout, -T pour les trois)
+#[derive(Clone, Copy, Debug)]
+pub struct Timeouts {
+    pub connect: Option<Duration>,
+    pub dns: Option<Duration>,
+    // Durée maximale sans recevoir le moindre octet, et non durée totale du téléchargement
+    pub read: Option<Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        // Comme GNU wget : 900 secondes d'inactivité, pas de limite pour la connexion ni le DNS
+        Timeouts {
+            connect: None,
+            dns: None,
+            read: Some(Duration::from_secs(900)),
+        }
+    }
+}
+
+impl Timeouts {
+    // 0 désactive le délai correspondant
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        let mut timeouts = Timeouts::default();
+        if let Some(&secs) = matches.get_one::<f64>("timeout") {
+            let all = seconds(secs);
+            timeouts = Timeouts {
+                connect: all,
+                dns: all,
+                read: all,
+            };
+        }
+        if let Some(&secs) = matches.get_one::<f64>("connect_timeout") {
+            timeouts.connect = seconds(secs);
+        }
+        if let Some(&secs) = matches.get_one::<f64>("dns_timeout") {
+            timeouts.dns = seconds(secs);
+        }
+        if let Some(&secs) = matches.get_one::<f64>("read_timeout") {
+            timeouts.read = seconds(secs);
+        }
+        timeouts
+    }
+
+    pub fn apply(&self, mut builder: ClientBuilder) -> ClientBuilder {
+        if let Some(connect) = self.connect {
+            builder = builder.connect_timeout(connect);
+        }
+        if let Some(limit) = self.dns {
+            builder = builder.dns_resolver(Arc::new(TimeoutResolver { limit }));
+        }
+        builder
+    }
+}
+
+fn seconds(secs: f64) -> Option<Duration> {
+    (secs > 0.0).then(|| Duration::from_secs_f64(secs))
+}
+
+// Attend `future` au plus `limit` : utilisé pour chaque morceau reçu et pour l'attente
+// des en-têtes de réponse. Un dépassement est une erreur temporaire (TimedOut).
+pub async fn idle<F: Future>(limit: Option<Duration>, future: F) -> io::Result<F::Output> {
+    let Some(limit) = limit else {
+        return Ok(future.await);
+    };
+    tokio::time::timeout(limit, future).await.map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("no data received for {} seconds", limit.as_secs_f32()),
+        )
+    })
+}
+
+// Résolution DNS du système, interrompue au-delà de --dns-timeout
+struct TimeoutResolver {
+    limit: Duration,
+}
+
+impl Resolve for TimeoutResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let limit = self.limit;
+        Box::pin(async move {
+            let host = name.as_str();
+            let addrs = tokio::time::timeout(limit, tokio::net::lookup_host(format!("{}:0", host)))
+                .await
+                .map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("DNS lookup of {} timed out", host),
+                    )
+                })??;
+            Ok(Box::new(addrs) as Addrs)
+        })
+    }
+}
@@ -0,0 +1,52 @@
+use filetime::{set_file_mtime, FileTime};
+use reqwest::header::{HeaderMap, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Mode -N : en-têtes conditionnels construits à partir de la copie locale.
+// Vide si le fichier n'existe pas encore.
+pub fn conditional_headers(path: &Path) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let modified = match fs::metadata(path).and_then(|meta| meta.modified()) {
+        Ok(modified) => modified,
+        Err(_) => return headers,
+    };
+
+    if let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(modified)) {
+        headers.insert(IF_MODIFIED_SINCE, value);
+    }
+    if let Some(etag) = fs::read_to_string(etag_path(path))
+        .ok()
+        .and_then(|etag| HeaderValue::from_str(etag.trim()).ok())
+    {
+        headers.insert(IF_NONE_MATCH, etag);
+    }
+    headers
+}
+
+// Après un téléchargement réussi : date du fichier = Last-Modified du serveur,
+// et ETag conservé pour la prochaine requête conditionnelle
+pub fn record(path: &Path, headers: &HeaderMap) -> std::io::Result<()> {
+    let last_modified = headers
+        .get(LAST_MODIFIED)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok());
+    if let Some(modified) = last_modified {
+        set_file_mtime(path, FileTime::from_system_time(modified))?;
+    }
+
+    match headers.get(ETAG).and_then(|value| value.to_str().ok()) {
+        Some(etag) => fs::write(etag_path(path), etag)?,
+        None => {
+            // Un ETag périmé ne doit pas survivre à une nouvelle version du fichier
+            let _ = fs::remove_file(etag_path(path));
+        }
+    }
+    Ok(())
+}
+
+// L'ETag est stocké à côté du fichier, dans `.<nom>.etag`
+fn etag_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{}.etag", name))
+}
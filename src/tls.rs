@@ -0,0 +1,255 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use clap::ArgMatches;
+use reqwest::tls::{Certificate, Identity, TlsInfo, Version};
+use reqwest::header::{HeaderValue, COOKIE};
+use reqwest::{Client, ClientBuilder, Response};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+use url::Url;
+
+// Réglages TLS appliqués à tous les clients (--ca-certificate, --certificate...)
+#[derive(Clone, Debug, Default)]
+pub struct TlsSettings {
+    // Autorités ajoutées à celles du système
+    ca_certificates: Vec<Certificate>,
+    // Certificat client pour l'authentification mutuelle
+    identity: Option<Identity>,
+    min_version: Option<Version>,
+    no_check_certificate: bool,
+}
+
+impl TlsSettings {
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, String> {
+        let mut ca_certificates = Vec::new();
+        if let Some(path) = matches.get_one::<String>("ca_certificate") {
+            ca_certificates.extend(read_certificates(Path::new(path))?);
+        }
+        if let Some(dir) = matches.get_one::<String>("ca_directory") {
+            let entries = fs::read_dir(dir).map_err(|e| format!("Cannot read {}: {}", dir, e))?;
+            // Les dossiers de certificats contiennent aussi des liens `<hash>.0` et
+            // d'autres fichiers : seuls les certificats lisibles sont retenus
+            for entry in entries.flatten() {
+                if entry.path().is_file() {
+                    ca_certificates.extend(read_certificates(&entry.path()).unwrap_or_default());
+                }
+            }
+        }
+
+        let identity = match matches.get_one::<String>("certificate") {
+            Some(certificate) => Some(read_identity(matches, certificate)?),
+            None => None,
+        };
+
+        let min_version = match matches.get_one::<String>("min_tls_version").map(String::as_str) {
+            None => None,
+            Some("1.0") => Some(Version::TLS_1_0),
+            Some("1.1") => Some(Version::TLS_1_1),
+            Some("1.2") => Some(Version::TLS_1_2),
+            // Le backend natif (OpenSSL, Schannel, Secure Transport) ne permet pas d'imposer 1.3
+            Some(other) => return Err(format!("Invalid TLS version: {} (expected 1.0, 1.1 or 1.2)", other)),
+        };
+
+        let no_check_certificate = matches.get_flag("no_check_certificate");
+        if no_check_certificate {
+            eprintln!("WARNING: certificate verification is disabled (--no-check-certificate)");
+        }
+
+        Ok(TlsSettings {
+            ca_certificates,
+            identity,
+            min_version,
+            no_check_certificate,
+        })
+    }
+
+    pub fn apply(&self, mut builder: ClientBuilder) -> ClientBuilder {
+        for certificate in &self.ca_certificates {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+        if let Some(identity) = &self.identity {
+            builder = builder.identity(identity.clone());
+        }
+        if let Some(version) = self.min_version {
+            builder = builder.min_tls_version(version);
+        }
+        if self.no_check_certificate {
+            builder = builder
+                .danger_accept_invalid_certs(true)
+                .danger_accept_invalid_hostnames(true);
+        }
+        builder
+    }
+}
+
+// Fichier PEM (un ou plusieurs certificats) ou DER
+fn read_certificates(path: &Path) -> Result<Vec<Certificate>, String> {
+    let data = fs::read(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    match Certificate::from_pem_bundle(&data) {
+        Ok(certificates) if !certificates.is_empty() => Ok(certificates),
+        _ => Certificate::from_der(&data)
+            .map(|certificate| vec![certificate])
+            .map_err(|_| format!("No certificate found in {}", path.display())),
+    }
+}
+
+// --certificate au format PEM (clé dans --private-key ou dans le même fichier)
+// ou PKCS#12 (extension .p12/.pfx, ou --certificate-type P12)
+fn read_identity(matches: &ArgMatches, certificate: &str) -> Result<Identity, String> {
+    let data = fs::read(certificate).map_err(|e| format!("Cannot read {}: {}", certificate, e))?;
+    let pkcs12 = match matches.get_one::<String>("certificate_type") {
+        Some(kind) if kind.eq_ignore_ascii_case("p12") => true,
+        Some(kind) if kind.eq_ignore_ascii_case("pem") => false,
+        Some(kind) => return Err(format!("Invalid certificate type: {} (expected PEM or P12)", kind)),
+        None => {
+            let lower = certificate.to_ascii_lowercase();
+            lower.ends_with(".p12") || lower.ends_with(".pfx")
+        }
+    };
+
+    if pkcs12 {
+        let password = matches
+            .get_one::<String>("certificate_password")
+            .map(String::as_str)
+            .unwrap_or_default();
+        return Identity::from_pkcs12_der(&data, password)
+            .map_err(|e| format!("Invalid PKCS#12 certificate {}: {}", certificate, e));
+    }
+
+    let key = match matches.get_one::<String>("private_key") {
+        Some(path) => fs::read(path).map_err(|e| format!("Cannot read {}: {}", path, e))?,
+        None => data.clone(),
+    };
+    Identity::from_pkcs8_pem(&data, &key).map_err(|e| {
+        format!(
+            "Invalid client certificate {}: {} (the private key must be PKCS#8 PEM)",
+            certificate, e
+        )
+    })
+}
+
+// --pinnedpubkey : empreintes SHA-256 des clés publiques (SubjectPublicKeyInfo)
+// acceptées pour le serveur
+#[derive(Clone, Debug)]
+pub struct PinnedKeys {
+    hashes: Vec<Vec<u8>>,
+    // Vérification de chaque origine par `probe`, partagée par tout le run : les
+    // requêtes simultanées vers une même origine attendent le même sondage
+    verified: Arc<Mutex<HashMap<String, Arc<OnceCell<()>>>>>,
+}
+
+impl PinnedKeys {
+    // `sha256//<base64>;sha256//<base64>`, ou un fichier de clé publique PEM ou DER
+    pub fn parse(value: &str) -> Result<Self, String> {
+        if value.starts_with("sha256//") {
+            let hashes = value
+                .split(';')
+                .map(|pin| {
+                    let encoded = pin
+                        .trim()
+                        .strip_prefix("sha256//")
+                        .ok_or_else(|| format!("Invalid pinned key: {}", pin))?;
+                    match STANDARD.decode(encoded) {
+                        Ok(hash) if hash.len() == 32 => Ok(hash),
+                        _ => Err(format!("Invalid sha256 pinned key: {}", pin)),
+                    }
+                })
+                .collect::<Result<_, String>>()?;
+            return Ok(PinnedKeys {
+                hashes,
+                verified: Arc::default(),
+            });
+        }
+
+        let data = fs::read(value).map_err(|e| format!("Cannot read {}: {}", value, e))?;
+        let der = match std::str::from_utf8(&data) {
+            Ok(text) if text.contains("-----BEGIN PUBLIC KEY-----") => {
+                let body: String = text
+                    .lines()
+                    .filter(|line| !line.starts_with("-----"))
+                    .collect();
+                STANDARD
+                    .decode(body.trim())
+                    .map_err(|_| format!("Invalid PEM public key in {}", value))?
+            }
+            _ => data,
+        };
+        Ok(PinnedKeys {
+            hashes: vec![Sha256::digest(&der).to_vec()],
+            verified: Arc::default(),
+        })
+    }
+
+    // Vérifie la clé de l'origine de `url` par une requête HEAD nue, avant de lui
+    // envoyer identifiants, cookies, en-têtes ou corps : native-tls ne permet pas
+    // d'intervenir pendant la poignée de main. Chaque origine n'est sondée qu'une
+    // fois ; les réponses suivantes restent vérifiées par `verify`. Le délai
+    // --read-timeout de la requête appelante s'applique aussi au sondage.
+    pub async fn probe(&self, client: &Client, url: &Url) -> Result<(), Box<dyn Error>> {
+        if url.scheme() != "https" {
+            return Ok(());
+        }
+        let cell = Arc::clone(
+            self.verified
+                .lock()
+                .unwrap()
+                .entry(url.origin().ascii_serialization())
+                .or_default(),
+        );
+        // Un échec laisse la cellule vide : la requête suivante sondera de nouveau
+        cell.get_or_try_init(async || -> Result<(), Box<dyn Error>> {
+            // Un en-tête Cookie vide empêche le client d'ajouter ceux du jar
+            let response = client
+                .head(url.clone())
+                .header(COOKIE, HeaderValue::from_static(""))
+                .send()
+                .await?;
+            self.verify(&response)?;
+            Ok(())
+        })
+        .await?;
+        Ok(())
+    }
+
+    // Vérifie la clé du certificat présenté par le serveur, avant de lire le corps
+    pub fn verify(&self, response: &Response) -> Result<(), PinnedKeyMismatch> {
+        if response.url().scheme() != "https" {
+            return Ok(());
+        }
+        let mismatch = || PinnedKeyMismatch {
+            host: response.url().host_str().unwrap_or_default().to_string(),
+        };
+        let certificate = response
+            .extensions()
+            .get::<TlsInfo>()
+            .and_then(|info| info.peer_certificate())
+            .ok_or_else(mismatch)?;
+        let (_, parsed) = x509_parser::parse_x509_certificate(certificate).map_err(|_| mismatch())?;
+        let hash = Sha256::digest(parsed.tbs_certificate.subject_pki.raw);
+        if self.hashes.iter().any(|pinned| pinned.as_slice() == hash.as_slice()) {
+            Ok(())
+        } else {
+            Err(mismatch())
+        }
+    }
+}
+
+// Le serveur présente une clé publique différente de celle attendue
+#[derive(Debug)]
+pub struct PinnedKeyMismatch {
+    pub host: String,
+}
+
+impl fmt::Display for PinnedKeyMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Public key of {} does not match --pinnedpubkey", self.host)
+    }
+}
+
+impl Error for PinnedKeyMismatch {}
@@ -1,5 +1,10 @@
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::AsyncWriteExt;
+
+use crate::naming::rotate_backups;
 
 // Fonction pour enregistrer les logs dans un fichier
 pub fn log_to_file(log_message: &str) {
@@ -13,3 +18,63 @@ pub fn log_to_file(log_message: &str) {
 
     file.write_all(log_entry.as_bytes()).expect("Unable to write log");
 }
+
+// Destination des messages d'état : le terminal, ou `wget-log` en arrière-plan
+#[derive(Clone, Copy, Debug)]
+pub enum Output {
+    Stdout,
+    // -O - : la sortie standard est réservée au contenu téléchargé
+    Stderr,
+    LogFile,
+    // Aucun affichage (téléchargements parallèles du mode -i)
+    Silent,
+}
+
+impl Output {
+    pub fn new(background: bool) -> Self {
+        if background {
+            Output::LogFile
+        } else {
+            Output::Stdout
+        }
+    }
+
+    pub fn print(self, message: &str) {
+        match self {
+            Output::Stdout => println!("{}", message),
+            Output::Stderr => eprintln!("{}", message),
+            Output::LogFile => log_to_file(message),
+            Output::Silent => {}
+        }
+    }
+}
+
+// Les téléchargements sont écrits dans `nom.part`, puis renommés une fois complets
+pub fn part_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".part");
+    PathBuf::from(name)
+}
+
+// Rend le fichier `.part` visible sous son nom définitif, après l'avoir synchronisé sur le disque
+pub async fn commit_part(path: &Path) -> std::io::Result<()> {
+    let part = part_path(path);
+    tokio::fs::File::open(&part).await?.sync_all().await?;
+    rotate_backups(path)?;
+    tokio::fs::rename(&part, path).await
+}
+
+// Écrit un contenu complet sans jamais exposer de fichier tronqué sous le nom final.
+// Chaque écriture a son propre fichier temporaire : deux tâches qui enregistrent le
+// même fichier ne se tronquent pas l'une l'autre, la dernière à renommer l'emporte.
+pub async fn write_atomically(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let mut temp = path.as_os_str().to_os_string();
+    temp.push(format!(".{}.part", NEXT.fetch_add(1, Ordering::Relaxed)));
+
+    let mut file = tokio::fs::File::create(&temp).await?;
+    file.write_all(content).await?;
+    file.sync_all().await?;
+    drop(file);
+    tokio::fs::rename(&temp, path).await
+}